@@ -0,0 +1,71 @@
+// Main-thread helper creating an AudioWorkletNode backed by `bpm-worklet.js`.
+//
+//     const node = await createBpmNode(context, { onMessage: console.log });
+//     source.connect(node);
+//
+// Messages have a `type` of "onset", "beat", "tempo", "tempoChange" or
+// "tempogram" and a `time` in the AudioContext's clock. `bands` is an
+// optional list of `[lowHz, highHz, weight]` triplets; onset messages then
+// carry a bit mask of the bands that fired. `downmix` selects how
+// multi-channel input is reduced to mono and defaults to averaging the
+// channels; with `perChannel` set each channel is analysed on its own and
+// their onsets are merged instead. `minBpm` and `maxBpm` bound the tempo
+// estimate and `preferredBpm` centres the prior that resolves half and
+// double tempo readings; pass `null` to disable it. With `tempogramInterval` set, "tempogram" messages carrying
+// a column of the tempogram over `bpms` are posted that many seconds apart.
+// Beat messages carry their `barPosition` in a bar of `beatsPerBar` beats,
+// 4 by default, and whether they are a `downbeat`. `analysisRate` sets the
+// rate the input is resampled to before analysis; pass `null` to analyse it
+// at the context's rate.
+
+const WORKLET_URL = new URL("./bpm-worklet.js", import.meta.url);
+const WASM_URL = new URL("../pkg/bpm_bg.wasm", import.meta.url);
+
+export async function createBpmNode(
+  context,
+  {
+    mode,
+    frameSize,
+    hopSize,
+    window,
+    downmix,
+    perChannel,
+    bands,
+    minBpm,
+    maxBpm,
+    preferredBpm,
+    tempogramInterval,
+    beatsPerBar,
+    analysisRate,
+    onMessage,
+  } = {},
+) {
+  const [module] = await Promise.all([
+    WebAssembly.compileStreaming(fetch(WASM_URL)),
+    context.audioWorklet.addModule(WORKLET_URL),
+  ]);
+  const node = new AudioWorkletNode(context, "bpm-processor", {
+    numberOfInputs: 1,
+    numberOfOutputs: 0,
+    processorOptions: {
+      module,
+      mode,
+      frameSize,
+      hopSize,
+      window,
+      downmix,
+      perChannel,
+      bands,
+      minBpm,
+      maxBpm,
+      preferredBpm,
+      tempogramInterval,
+      beatsPerBar,
+      analysisRate,
+    },
+  });
+  if (onMessage) {
+    node.port.onmessage = (event) => onMessage(event.data);
+  }
+  return node;
+}
@@ -0,0 +1,158 @@
+// AudioWorklet processor running onset and tempo detection on the audio
+// thread.
+//
+// Build the wasm package for ES module targets first:
+//
+//     wasm-pack build --target web
+//
+// then load it from the main thread with `createBpmNode` in `bpm-node.js`.
+// The compiled module is handed over through `processorOptions` because
+// worklets cannot fetch it themselves.
+
+import { initSync, OnsetDetector } from "../pkg/bpm.js";
+
+// Only post a tempo update when the estimate moves by more than this many BPM.
+const TEMPO_CHANGE_BPM = 0.5;
+
+class BpmProcessor extends AudioWorkletProcessor {
+  constructor(options) {
+    super();
+    const {
+      module,
+      mode,
+      frameSize,
+      hopSize,
+      window,
+      downmix,
+      perChannel,
+      bands,
+      minBpm,
+      maxBpm,
+      preferredBpm,
+      tempogramInterval,
+      beatsPerBar,
+      analysisRate,
+    } = options.processorOptions;
+    initSync({ module });
+    this.detector = new OnsetDetector(mode, sampleRate, frameSize, hopSize);
+    // Set first since they reset the detector. `null` analyses the input at
+    // the context's rate.
+    if (analysisRate !== undefined) {
+      this.detector.analysisRate = analysisRate ?? undefined;
+    }
+    if (perChannel !== undefined) {
+      this.detector.perChannel = perChannel;
+    }
+    if (window !== undefined) {
+      this.detector.window = window;
+    }
+    if (downmix !== undefined) {
+      this.detector.downmix = downmix;
+    }
+    if (beatsPerBar !== undefined) {
+      this.detector.beatsPerBar = beatsPerBar;
+    }
+    // Both bounds are set together so no intermediate range is empty.
+    if (minBpm !== undefined || maxBpm !== undefined) {
+      this.detector.setTempoRange(
+        minBpm ?? this.detector.minBpm,
+        maxBpm ?? this.detector.maxBpm
+      );
+    }
+    // `null` disables the prior.
+    if (preferredBpm !== undefined) {
+      this.detector.preferredBpm = preferredBpm ?? undefined;
+    }
+    if (bands) {
+      this.detector.setBands(Float32Array.from(bands.flat()));
+    }
+    this.planar = new Float32Array(128);
+    this.tempo = undefined;
+    this.tempogramInterval = tempogramInterval;
+    this.tempogramBpms = this.detector.tempogramBpms;
+    this.nextTempogram = 0;
+  }
+
+  // Copies the channels back to back so they can be handed to the detector,
+  // which downmixes or splits them, in one call.
+  planarize(channels) {
+    const length = channels[0].length;
+    if (this.planar.length !== length * channels.length) {
+      this.planar = new Float32Array(length * channels.length);
+    }
+    channels.forEach((channel, i) => this.planar.set(channel, i * length));
+    return this.planar;
+  }
+
+  process(inputs) {
+    const channels = inputs[0];
+    if (!channels || channels.length === 0) {
+      return true;
+    }
+
+    const onset = this.detector.processPlanar(
+      this.planarize(channels),
+      channels.length
+    );
+    if (onset) {
+      this.port.postMessage({
+        type: "onset",
+        time: currentTime,
+        onsetTime: this.detector.onsetTime,
+        strength: this.detector.onsetStrength,
+        threshold: this.detector.onsetThreshold,
+        bands: this.detector.onsetBands,
+      });
+    }
+    if (this.detector.beat) {
+      this.port.postMessage({
+        type: "beat",
+        time: currentTime,
+        downbeat: this.detector.downbeat,
+        barPosition: this.detector.barPosition,
+      });
+    }
+
+    const tempoChange = this.detector.tempoChange;
+    if (tempoChange !== undefined) {
+      this.port.postMessage({
+        type: "tempoChange",
+        time: currentTime,
+        bpm: tempoChange,
+      });
+    }
+
+    const tempo = this.detector.tempo;
+    if (
+      tempo !== undefined &&
+      (this.tempo === undefined || Math.abs(tempo - this.tempo) > TEMPO_CHANGE_BPM)
+    ) {
+      this.tempo = tempo;
+      this.port.postMessage({
+        type: "tempo",
+        time: currentTime,
+        bpm: tempo,
+        confidence: this.detector.tempoConfidence,
+      });
+    }
+
+    if (this.tempogramInterval && currentTime >= this.nextTempogram) {
+      this.nextTempogram = currentTime + this.tempogramInterval;
+      const column = this.detector.tempogramColumn();
+      if (column) {
+        this.port.postMessage(
+          {
+            type: "tempogram",
+            time: currentTime,
+            bpms: this.tempogramBpms,
+            column,
+          },
+          [column.buffer]
+        );
+      }
+    }
+    return true;
+  }
+}
+
+registerProcessor("bpm-processor", BpmProcessor);
@@ -0,0 +1,119 @@
+use super::bpm::{adaptive_threshold, previous_peak, ThresholdParams};
+use super::ring::RingBuffer;
+
+// Largest number of bands, so fired bands fit in an `OnsetEvent` bit mask.
+pub const MAX_BANDS: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Band {
+    pub low_hz: f32,
+    pub high_hz: f32,
+    // Contribution of the band's ODF to the broadband ODF.
+    pub weight: f32,
+}
+
+impl Band {
+    pub fn new(low_hz: f32, high_hz: f32, weight: f32) -> Self {
+        Self {
+            low_hz,
+            high_hz,
+            weight,
+        }
+    }
+
+    // Spectral flux over the bins of the band, where `bin_hz` is the width of
+    // one bin.
+    pub fn flux(&self, prev: &[f32], curr: &[f32], bin_hz: f32) -> f32 {
+        let low = (self.low_hz / bin_hz).ceil() as usize;
+        let high = ((self.high_hz / bin_hz).ceil() as usize).min(curr.len());
+        if low >= high {
+            return 0.;
+        }
+        prev[low..high]
+            .iter()
+            .zip(curr[low..high].iter())
+            .map(|(p, c)| (c - p).max(0.))
+            .sum()
+    }
+}
+
+// Runs peak picking with its own adaptive threshold over one band's ODF.
+pub struct BandDetector {
+    band: Band,
+    history: RingBuffer<f32>,
+    threshold: f32,
+    highest_peak: f32,
+}
+
+impl BandDetector {
+    pub fn new(band: Band, params: &ThresholdParams) -> Self {
+        Self {
+            band,
+            history: RingBuffer::new(history_capacity(params)),
+            threshold: 0.,
+            highest_peak: 0.,
+        }
+    }
+
+    pub fn band(&self) -> &Band {
+        &self.band
+    }
+
+    pub fn set_threshold_params(&mut self, params: &ThresholdParams) {
+        self.history.set_capacity(history_capacity(params));
+    }
+
+    pub fn odf(&self, prev: &[f32], curr: &[f32], bin_hz: f32) -> f32 {
+        self.band.flux(prev, curr, bin_hz)
+    }
+
+    // Adds the band's ODF value for the current frame and, if `check` is
+    // set, returns whether the previous frame was an onset in this band.
+    pub fn update(&mut self, odf: f32, params: &ThresholdParams, check: bool) -> bool {
+        self.history.push(odf);
+        self.threshold = adaptive_threshold(&self.history, self.highest_peak, params);
+        if !check {
+            return false;
+        }
+        match previous_peak(&self.history, self.threshold) {
+            Some(peak) => {
+                self.highest_peak = self.highest_peak.max(peak);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn history_capacity(params: &ThresholdParams) -> usize {
+    params.m.max(3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_band_flux() {
+        // 10 Hz bins, the band covers bins 2 up to and excluding 5.
+        let band = Band::new(20., 50., 1.);
+        let prev = [0.; 8];
+        let mut curr = [1.; 8];
+        assert_eq!(band.flux(&prev, &curr, 10.), 3.);
+        curr[3] = 0.;
+        assert_eq!(band.flux(&prev, &curr, 10.), 2.);
+        assert_eq!(band.flux(&curr, &prev, 10.), 0.);
+        assert_eq!(Band::new(100., 200., 1.).flux(&prev, &curr, 10.), 0.);
+    }
+
+    #[test]
+    fn test_band_detector_update() {
+        let params = ThresholdParams::default();
+        let mut detector = BandDetector::new(Band::new(0., 100., 1.), &params);
+        assert!(!detector.update(0., &params, true));
+        assert!(!detector.update(5., &params, true));
+        assert!(detector.update(0., &params, true));
+        assert!(!detector.update(5., &params, false));
+        assert!(!detector.update(0., &params, false));
+    }
+}
@@ -0,0 +1,267 @@
+// Fraction of the phase error applied each time an onset is observed near
+// a predicted beat.
+const PHASE_CORRECTION: f32 = 0.25;
+// Onsets further than this fraction of a period from the nearest predicted
+// beat are treated as off-beat and ignored.
+const PHASE_TOLERANCE: f32 = 0.25;
+// Factor by which the onset strength supporting a beat phase decays per
+// onset.
+const SUPPORT_DECAY: f32 = 0.8;
+// Off-beat onsets that agree on a phase move the beat grid onto it once there
+// are this many of them and their support exceeds that of the current grid
+// by `RIVAL_MARGIN`.
+const RIVAL_ONSETS: usize = 3;
+const RIVAL_MARGIN: f32 = 1.5;
+// Factor by which the accumulated accent of each bar position decays per bar.
+const ACCENT_DECAY: f32 = 0.9;
+
+// Predicts beat positions (in ODF frames) from a beat period and corrects the
+// phase from detected onsets, like a simple phase-locked loop.
+pub struct BeatTracker {
+    period: Option<f32>,
+    next_beat: Option<f32>,
+    // Decaying strength of the onsets near the predicted beats.
+    support: f32,
+    // Phase that recent off-beat onsets agree on.
+    rival: Option<Rival>,
+}
+
+struct Rival {
+    // Offset from the predicted beats in frames.
+    offset: f32,
+    support: f32,
+    onsets: usize,
+}
+
+impl BeatTracker {
+    pub fn new() -> Self {
+        Self {
+            period: None,
+            next_beat: None,
+            support: 0.,
+            rival: None,
+        }
+    }
+
+    pub fn set_period(&mut self, period: f32) {
+        self.period = Some(period);
+    }
+
+    pub fn period(&self) -> Option<f32> {
+        self.period
+    }
+
+    // Frame index at which the next beat is expected.
+    pub fn next_beat(&self) -> Option<f32> {
+        self.next_beat
+    }
+
+    // Feeds an onset located at `frame` with the given strength. The first
+    // onset after the period is known anchors the beat grid; later ones nudge
+    // its phase, or move the grid to another phase that is better supported.
+    pub fn onset(&mut self, frame: f32, strength: f32) {
+        let period = match self.period {
+            Some(period) => period,
+            None => return,
+        };
+        let next_beat = match self.next_beat {
+            Some(next_beat) => next_beat,
+            None => {
+                self.next_beat = Some(frame + period);
+                self.support = strength;
+                return;
+            }
+        };
+        // The onset may belong to the beat that was just emitted or to the
+        // upcoming one.
+        let previous_beat = next_beat - period;
+        let error = match (frame - previous_beat).abs() < (next_beat - frame).abs() {
+            true => frame - previous_beat,
+            false => frame - next_beat,
+        };
+        let tolerance = PHASE_TOLERANCE * period;
+        self.support *= SUPPORT_DECAY;
+        if let Some(rival) = &mut self.rival {
+            rival.support *= SUPPORT_DECAY;
+        }
+        if error.abs() <= tolerance {
+            self.next_beat = Some(next_beat + PHASE_CORRECTION * error);
+            self.support += strength;
+            return;
+        }
+
+        // Off-beat onsets can outweigh the grid when it was anchored on an
+        // off-beat in the first place.
+        let rival = match self.rival.take() {
+            Some(rival) if wrap(error - rival.offset, period).abs() <= tolerance => Rival {
+                offset: rival.offset + PHASE_CORRECTION * wrap(error - rival.offset, period),
+                support: rival.support + strength,
+                onsets: rival.onsets + 1,
+            },
+            _ => Rival {
+                offset: error,
+                support: strength,
+                onsets: 1,
+            },
+        };
+        match rival.onsets >= RIVAL_ONSETS && rival.support > RIVAL_MARGIN * self.support {
+            true => {
+                self.next_beat = Some(next_beat + rival.offset);
+                self.support = rival.support;
+            }
+            false => self.rival = Some(rival),
+        }
+    }
+
+    // Moves the tracker to `frame`, returning whether a beat was predicted to
+    // fall on or before it.
+    pub fn advance(&mut self, frame: usize) -> bool {
+        let (period, mut next_beat) = match (self.period, self.next_beat) {
+            (Some(period), Some(next_beat)) => (period, next_beat),
+            _ => return false,
+        };
+        let mut beat = false;
+        while next_beat <= frame as f32 {
+            next_beat += period;
+            beat = true;
+        }
+        self.next_beat = Some(next_beat);
+        beat
+    }
+}
+
+// Wraps a phase difference in frames to within half a period of zero.
+fn wrap(offset: f32, period: f32) -> f32 {
+    offset - period * (offset / period).round()
+}
+
+// Assigns beats to positions in a bar of `beats_per_bar` beats. Accents,
+// such as increases in low-frequency energy from kick drums, are collected
+// around each beat and accumulated per bar position; the position with the
+// strongest accents is taken as the downbeat.
+pub struct BarTracker {
+    beats_per_bar: usize,
+    // Decaying sum of the accents at each position of the beat cycle.
+    strengths: Vec<f32>,
+    // Position in the beat cycle of the last beat.
+    position: Option<usize>,
+    // Strongest accent nearest to the last beat, and nearest to the next one.
+    accents: (f32, f32),
+}
+
+impl BarTracker {
+    pub fn new(beats_per_bar: usize) -> Self {
+        assert!(beats_per_bar > 0, "a bar needs at least one beat");
+        Self {
+            beats_per_bar,
+            strengths: vec![0.; beats_per_bar],
+            position: None,
+            accents: (0., 0.),
+        }
+    }
+
+    // Feeds the accent of one frame, attributing it to the last beat or, if
+    // `near_next_beat`, to the upcoming one.
+    pub fn accent(&mut self, accent: f32, near_next_beat: bool) {
+        let nearest = match near_next_beat {
+            true => &mut self.accents.1,
+            false => &mut self.accents.0,
+        };
+        *nearest = nearest.max(accent);
+    }
+
+    // Records a beat. The accents around the previous beat are complete at
+    // this point and are added to its position.
+    pub fn beat(&mut self) {
+        let position = match self.position {
+            Some(position) => {
+                self.strengths[position] = ACCENT_DECAY * self.strengths[position] + self.accents.0;
+                (position + 1) % self.beats_per_bar
+            }
+            None => 0,
+        };
+        self.position = Some(position);
+        self.accents = (self.accents.1, 0.);
+    }
+
+    // Position of the last beat in its bar, from 1 for the downbeat to
+    // `beats_per_bar`.
+    pub fn bar_position(&self) -> Option<usize> {
+        // Ties, as before any accents are seen, go to the first position.
+        let downbeat = (0..self.beats_per_bar)
+            .rev()
+            .max_by(|&a, &b| self.strengths[a].total_cmp(&self.strengths[b]))?;
+        self.position
+            .map(|position| (position + self.beats_per_bar - downbeat) % self.beats_per_bar + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_beats_follow_period() {
+        let mut tracker = BeatTracker::new();
+        tracker.onset(5., 1.);
+        assert_eq!(tracker.next_beat(), None);
+        tracker.set_period(10.);
+        tracker.onset(5., 1.);
+        let beats: Vec<usize> = (0..40).filter(|&frame| tracker.advance(frame)).collect();
+        assert_eq!(beats, vec![15, 25, 35]);
+    }
+
+    #[test]
+    fn test_phase_correction() {
+        let mut tracker = BeatTracker::new();
+        tracker.set_period(10.);
+        tracker.onset(0., 1.);
+        assert_eq!(tracker.next_beat(), Some(10.));
+        // A late onset pulls the grid forward.
+        tracker.onset(12., 1.);
+        assert_eq!(tracker.next_beat(), Some(10.5));
+        // An off-beat onset is ignored.
+        tracker.onset(15., 1.);
+        assert_eq!(tracker.next_beat(), Some(10.5));
+    }
+
+    #[test]
+    fn test_grid_moves_to_stronger_phase() {
+        let mut tracker = BeatTracker::new();
+        tracker.set_period(10.);
+        // Anchored on a weak off-beat, followed by strong beats with weak
+        // off-beats between them.
+        tracker.onset(5., 0.5);
+        for beat in 1..8 {
+            let frame = 10 * beat;
+            tracker.advance(frame - 1);
+            tracker.onset(frame as f32, 2.);
+            tracker.onset(frame as f32 + 5., 0.5);
+        }
+        let next_beat = tracker.next_beat().unwrap();
+        assert!(wrap(next_beat, 10.).abs() < 1., "{}", next_beat);
+    }
+
+    #[test]
+    fn test_bar_position_follows_accents() {
+        let mut tracker = BarTracker::new(4);
+        assert_eq!(tracker.bar_position(), None);
+        let positions: Vec<usize> = (0..16)
+            .map(|beat| {
+                tracker.beat();
+                // Accent every fourth beat, starting with the third.
+                let accent = match beat % 4 {
+                    2 => 1.,
+                    _ => 0.1,
+                };
+                tracker.accent(accent, false);
+                // Frames before the next beat carry no accent.
+                tracker.accent(0., true);
+                tracker.bar_position().unwrap()
+            })
+            .collect();
+        // Counting starts from the first beat until accents are seen.
+        assert_eq!(positions[..3], [1, 2, 3]);
+        assert_eq!(positions[12..], [3, 4, 1, 2]);
+    }
+}
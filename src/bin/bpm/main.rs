@@ -0,0 +1,215 @@
+// Command line onset and tempo analysis of WAV files.
+//
+//     cargo run --features cli -- [--json] [--offline] [--mode MODE] FILE...
+
+mod wav;
+
+use bpm::{analyze_with_config, estimate_tempo};
+use bpm::{FrameProcessor, FrameProcessorConfig, OnsetDetectionMode};
+use bpm::{OnsetEvent, Tempo, TempoChange};
+use std::process;
+use wav::Wav;
+
+const USAGE: &str = "usage: bpm [--json] [--offline] [--mode MODE] FILE...
+
+Prints the onsets and tempo of each WAV file. The tempo is estimated over
+the whole file in both modes.
+
+options:
+  --json         print a JSON array with one object per file
+  --offline      analyse whole files with non-causal peak picking
+  --mode MODE    onset detection function: energy (default), spectral,
+                 hfc, complex or phase";
+
+struct Options {
+    json: bool,
+    offline: bool,
+    mode: OnsetDetectionMode,
+    files: Vec<String>,
+}
+
+struct Report {
+    file: String,
+    tempo: Option<Tempo>,
+    tempo_changes: Vec<TempoChange>,
+    onsets: Vec<OnsetEvent>,
+}
+
+fn parse_mode(name: &str) -> Option<OnsetDetectionMode> {
+    match name {
+        "energy" => Some(OnsetDetectionMode::Energy),
+        "spectral" => Some(OnsetDetectionMode::SpectralDifference),
+        "hfc" => Some(OnsetDetectionMode::HighFrequencyContent),
+        "complex" => Some(OnsetDetectionMode::ComplexDomain),
+        "phase" => Some(OnsetDetectionMode::PhaseDeviation),
+        _ => None,
+    }
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        json: false,
+        offline: false,
+        mode: OnsetDetectionMode::Energy,
+        files: vec![],
+    };
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--offline" => options.offline = true,
+            "--mode" => {
+                let name = args.next().ok_or("--mode needs a value")?;
+                options.mode =
+                    parse_mode(&name).ok_or_else(|| format!("unknown mode `{}`", name))?;
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option `{}`", arg)),
+            _ => options.files.push(arg),
+        }
+    }
+    if options.files.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    Ok(options)
+}
+
+fn analyze_file(file: &str, options: &Options) -> Result<Report, String> {
+    let bytes = std::fs::read(file).map_err(|error| format!("{}: {}", file, error))?;
+    let wav = Wav::decode(&bytes).map_err(|error| format!("{}: {}", file, error))?;
+    let signal = wav.to_mono();
+    let config = FrameProcessorConfig {
+        mode: options.mode,
+        sample_rate: wav.sample_rate as f32,
+        ..FrameProcessorConfig::default()
+    };
+
+    let (tempo, tempo_changes, onsets) = match options.offline {
+        true => {
+            let analysis = analyze_with_config(&signal, config);
+            (analysis.tempo, analysis.tempo_changes, analysis.onsets)
+        }
+        false => {
+            let mut processor = FrameProcessor::from_config(config);
+            let result = processor.process(&signal);
+            // `FrameProcessor::tempo` only covers the last few seconds, so
+            // the reported tempo comes from the ODF of the whole file.
+            let tempo = estimate_tempo(&processor.odf_curve(&signal), processor.config());
+            (tempo, result.tempo_changes, result.onsets)
+        }
+    };
+    Ok(Report {
+        file: file.to_string(),
+        tempo,
+        tempo_changes,
+        onsets,
+    })
+}
+
+fn print_text(report: &Report) {
+    println!("{}", report.file);
+    match report.tempo {
+        Some(tempo) => println!(
+            "tempo: {:.2} BPM (confidence {:.2})",
+            tempo.bpm, tempo.confidence
+        ),
+        None => println!("tempo: unknown"),
+    }
+    for change in &report.tempo_changes {
+        println!(
+            "tempo change: {:.3}s {:.2} -> {:.2} BPM",
+            change.time, change.from_bpm, change.to_bpm
+        );
+    }
+    for onset in &report.onsets {
+        println!("onset: {:.3}s strength {:.3}", onset.time, onset.strength);
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn to_json(report: &Report) -> String {
+    let tempo = match report.tempo {
+        Some(tempo) => format!(
+            "{{\"bpm\":{},\"confidence\":{}}}",
+            tempo.bpm, tempo.confidence
+        ),
+        None => "null".to_string(),
+    };
+    let tempo_changes: Vec<String> = report
+        .tempo_changes
+        .iter()
+        .map(|change| {
+            format!(
+                "{{\"time\":{},\"from\":{},\"to\":{}}}",
+                change.time, change.from_bpm, change.to_bpm
+            )
+        })
+        .collect();
+    let onsets: Vec<String> = report
+        .onsets
+        .iter()
+        .map(|onset| {
+            format!(
+                "{{\"time\":{},\"sample\":{},\"strength\":{},\"threshold\":{}}}",
+                onset.time, onset.sample, onset.strength, onset.threshold
+            )
+        })
+        .collect();
+    format!(
+        "{{\"file\":{},\"tempo\":{},\"tempoChanges\":[{}],\"onsets\":[{}]}}",
+        json_string(&report.file),
+        tempo,
+        tempo_changes.join(","),
+        onsets.join(",")
+    )
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+
+    let mut failed = false;
+    let mut reports = vec![];
+    for file in &options.files {
+        match analyze_file(file, &options) {
+            Ok(report) => reports.push(report),
+            Err(message) => {
+                eprintln!("{}", message);
+                failed = true;
+            }
+        }
+    }
+
+    match options.json {
+        true => {
+            let objects: Vec<String> = reports.iter().map(to_json).collect();
+            println!("[{}]", objects.join(","));
+        }
+        false => {
+            for report in &reports {
+                print_text(report);
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
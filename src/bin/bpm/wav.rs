@@ -0,0 +1,206 @@
+use std::fmt;
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_IEEE_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xfffe;
+
+#[derive(Debug, PartialEq)]
+pub enum WavError {
+    NotRiffWave,
+    MissingChunk(&'static str),
+    Unsupported { format: u16, bits_per_sample: u16 },
+    Truncated,
+    ZeroSampleRate,
+}
+
+impl fmt::Display for WavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WavError::NotRiffWave => write!(f, "not a RIFF/WAVE file"),
+            WavError::MissingChunk(id) => write!(f, "missing `{}` chunk", id),
+            WavError::Unsupported {
+                format,
+                bits_per_sample,
+            } => write!(
+                f,
+                "unsupported sample format {} with {} bits per sample",
+                format, bits_per_sample
+            ),
+            WavError::Truncated => write!(f, "file is truncated"),
+            WavError::ZeroSampleRate => write!(f, "sample rate is zero"),
+        }
+    }
+}
+
+pub struct Wav {
+    pub sample_rate: u32,
+    pub channels: u16,
+    // Interleaved samples scaled to [-1, 1].
+    pub samples: Vec<f32>,
+}
+
+impl Wav {
+    pub fn decode(bytes: &[u8]) -> Result<Self, WavError> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err(WavError::NotRiffWave);
+        }
+
+        let mut format = None;
+        let mut data = None;
+        let mut offset = 12;
+        while offset + 8 <= bytes.len() {
+            let id = &bytes[offset..offset + 4];
+            let size = read_u32(bytes, offset + 4) as usize;
+            let start = offset + 8;
+            let end = start.checked_add(size).ok_or(WavError::Truncated)?;
+            // Some writers leave the data chunk size unset when streaming.
+            let body = &bytes[start..end.min(bytes.len())];
+            match id {
+                b"fmt " => format = Some(body),
+                b"data" => data = Some(body),
+                _ => {}
+            }
+            // Chunks are padded to an even number of bytes.
+            offset = end + size % 2;
+        }
+
+        let format = format.ok_or(WavError::MissingChunk("fmt "))?;
+        let data = data.ok_or(WavError::MissingChunk("data"))?;
+        if format.len() < 16 {
+            return Err(WavError::Truncated);
+        }
+        let mut format_tag = read_u16(format, 0);
+        let channels = read_u16(format, 2);
+        let sample_rate = read_u32(format, 4);
+        let bits_per_sample = read_u16(format, 14);
+        if format_tag == FORMAT_EXTENSIBLE {
+            if format.len() < 26 {
+                return Err(WavError::Truncated);
+            }
+            // The sub-format GUID starts with the actual format tag.
+            format_tag = read_u16(format, 24);
+        }
+
+        let unsupported = WavError::Unsupported {
+            format: format_tag,
+            bits_per_sample,
+        };
+        let decode: fn(&[u8]) -> f32 = match (format_tag, bits_per_sample) {
+            (FORMAT_PCM, 8) => |b| (b[0] as f32 - 128.) / 128.,
+            (FORMAT_PCM, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.,
+            (FORMAT_PCM, 24) => {
+                |b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8388608.
+            }
+            (FORMAT_PCM, 32) => {
+                |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2147483648.
+            }
+            (FORMAT_IEEE_FLOAT, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            _ => return Err(unsupported),
+        };
+        if channels == 0 {
+            return Err(unsupported);
+        }
+        if sample_rate == 0 {
+            return Err(WavError::ZeroSampleRate);
+        }
+
+        let samples = data
+            .chunks_exact(bits_per_sample as usize / 8)
+            .map(decode)
+            .collect();
+        Ok(Self {
+            sample_rate,
+            channels,
+            samples,
+        })
+    }
+
+    // Averages the channels of each sample frame.
+    pub fn to_mono(&self) -> Vec<f32> {
+        let channels = self.channels as usize;
+        self.samples
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect()
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav_bytes(format: u16, channels: u16, bits_per_sample: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&format.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&8000u32.to_le_bytes());
+        let block_align = channels * bits_per_sample / 8;
+        bytes.extend_from_slice(&(8000 * block_align as u32).to_le_bytes());
+        bytes.extend_from_slice(&block_align.to_le_bytes());
+        bytes.extend_from_slice(&bits_per_sample.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn test_decode_pcm16_stereo() {
+        let data: Vec<u8> = [16384i16, -16384, 32767, 32767]
+            .iter()
+            .flat_map(|x| x.to_le_bytes().to_vec())
+            .collect();
+        let wav = Wav::decode(&wav_bytes(FORMAT_PCM, 2, 16, &data)).unwrap();
+        assert_eq!(wav.sample_rate, 8000);
+        assert_eq!(wav.channels, 2);
+        assert_eq!(
+            wav.samples,
+            vec![0.5, -0.5, 32767. / 32768., 32767. / 32768.]
+        );
+        assert_eq!(wav.to_mono(), vec![0., 32767. / 32768.]);
+    }
+
+    #[test]
+    fn test_decode_float_and_24_bit() {
+        let data = 0.25f32.to_le_bytes();
+        let wav = Wav::decode(&wav_bytes(FORMAT_IEEE_FLOAT, 1, 32, &data)).unwrap();
+        assert_eq!(wav.samples, vec![0.25]);
+
+        let data = [0x00, 0x00, 0xc0];
+        let wav = Wav::decode(&wav_bytes(FORMAT_PCM, 1, 24, &data)).unwrap();
+        assert_eq!(wav.samples, vec![-0.5]);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(Wav::decode(b"nope").err(), Some(WavError::NotRiffWave));
+        assert_eq!(
+            Wav::decode(&wav_bytes(FORMAT_IEEE_FLOAT, 1, 64, &[])).err(),
+            Some(WavError::Unsupported {
+                format: FORMAT_IEEE_FLOAT,
+                bits_per_sample: 64
+            })
+        );
+        let mut bytes = wav_bytes(FORMAT_PCM, 1, 16, &[]);
+        bytes[24..28].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(Wav::decode(&bytes).err(), Some(WavError::ZeroSampleRate));
+    }
+}
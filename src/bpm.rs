@@ -1,61 +1,78 @@
 #![allow(unused, dead_code)]
+use super::bands::{Band, BandDetector, MAX_BANDS};
+use super::beat::{BarTracker, BeatTracker};
+use super::fft::{real_fft, Complex};
+use super::resample::Resampler;
+use super::ring::RingBuffer;
+use super::tempo::{Tempo, TempoEstimator, TempoParams};
+use super::tracking::{TempoChange, TempoTracker, TrackingParams};
 use super::utils::{mean, median};
+use super::window::Window;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
 use std::fmt;
+use wasm_bindgen::prelude::*;
 
-const BUFFER_SIZE: usize = 512;
-const BUFFERS_PER_FRAME: usize = 4;
-const FRAME_SIZE: usize = BUFFER_SIZE * BUFFERS_PER_FRAME;
-
-#[derive(Clone, Copy)]
-struct FrameSlice([f32; BUFFER_SIZE]);
-
-impl FrameSlice {
-    fn new() -> Self {
-        Self([0.; BUFFER_SIZE])
-    }
-    fn from_array(data: [f32; BUFFER_SIZE]) -> Self {
-        Self(data)
-    }
-    fn buffer(&self) -> &[f32; BUFFER_SIZE] {
-        &self.0
-    }
-    fn release_buffer(&self) -> [f32; BUFFER_SIZE] {
-        self.0
-    }
-}
+pub const DEFAULT_HOP_SIZE: usize = 512;
+pub const DEFAULT_FRAME_SIZE: usize = DEFAULT_HOP_SIZE * 4;
+pub const DEFAULT_SAMPLE_RATE: f32 = 44100.;
+pub const DEFAULT_ANALYSIS_RATE: f32 = 44100.;
+pub const DEFAULT_BEATS_PER_BAR: usize = 4;
+// Upper edge of the band whose energy increases mark accented beats.
+const ACCENT_BAND_HZ: f32 = 150.;
+// Length of the most recent ODF history used for tempo estimation. It is
+// extended to three periods of the slowest allowed tempo when that is longer.
+const TEMPO_HORIZON_SECONDS: f32 = 6.;
 
 #[derive(Clone)]
 struct Frame {
-    samples: Vec<FrameSlice>,
+    samples: Vec<f32>,
+    window: Vec<f32>,
 }
 
 impl Frame {
-    fn new() -> Self {
-        let mut samples = vec![];
-        for i in 0..BUFFERS_PER_FRAME {
-            samples.push((FrameSlice::new()));
+    fn new(size: usize, window: Window) -> Self {
+        Self {
+            samples: vec![0.; size],
+            window: window.coefficients(size),
         }
-        Self { samples }
     }
 
-    fn write(&mut self, sample: [f32; BUFFER_SIZE]) -> [f32; BUFFER_SIZE] {
-        self.samples.push(FrameSlice::from_array(sample));
-        let old_slice = self.samples.remove(0);
-        old_slice.release_buffer()
+    fn set_window(&mut self, window: Window) {
+        self.window = window.coefficients(self.samples.len());
     }
 
-    fn buffer(&self) -> [f32; FRAME_SIZE] {
-        let mut buffer = [0.; FRAME_SIZE];
-        for (i, sample) in self.samples.iter().enumerate() {
-            let start = i * BUFFER_SIZE;
-            let mut slice = &mut buffer[start..(start + BUFFER_SIZE)];
-            slice.copy_from_slice(sample.buffer());
-        }
-        buffer
+    // Appends `samples` to the end of the frame and returns the same number
+    // of samples shifted out of its start.
+    fn write(&mut self, samples: &[f32]) -> Vec<f32> {
+        let count = samples.len().min(self.samples.len());
+        let released = self.samples.drain(..count).collect();
+        self.samples
+            .extend_from_slice(&samples[samples.len() - count..]);
+        released
+    }
+
+    fn buffer(&self) -> &[f32] {
+        &self.samples
+    }
+
+    fn windowed(&self) -> Vec<f32> {
+        self.samples
+            .iter()
+            .zip(self.window.iter())
+            .map(|(x, w)| x * w)
+            .collect()
     }
 
     fn energy(&self) -> f32 {
-        self.buffer().iter().fold(0., |acc, x| acc + x * x)
+        self.windowed().iter().fold(0., |acc, x| acc + x * x)
+    }
+
+    // Spectrum of the windowed frame, zero-padded to the next power of two.
+    fn complex_spectrum(&self) -> Vec<Complex> {
+        let mut windowed = self.windowed();
+        windowed.resize(self.samples.len().next_power_of_two(), 0.);
+        real_fft(&windowed)
     }
 }
 
@@ -70,108 +87,892 @@ impl fmt::Display for Frame {
     }
 }
 
-#[derive(PartialEq, Debug)]
-enum OnsetDetectionMode {
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OnsetDetectionMode {
     Energy,
     SpectralDifference,
+    // Increase in frequency-weighted spectral energy, which emphasises
+    // percussive attacks.
+    HighFrequencyContent,
+    // Deviation of each bin from the magnitude and phase predicted from the
+    // two previous hops, which also catches soft pitched onsets.
+    ComplexDomain,
+    // Mean absolute second-order phase difference across bins, for tonal
+    // onsets with little change in energy.
+    PhaseDeviation,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OnsetEvent {
+    // Index of the ODF frame the onset was found in.
+    pub frame: usize,
+    // Position of the onset in input samples, counted from the first sample
+    // given to the processor. It is corrected for where in the analysed
+    // frames the ODF responds, see `FrameProcessorConfig::onset_position`,
+    // and is typically within about a hop of the attack.
+    pub sample: usize,
+    // Position of the onset in seconds, counted from the first sample.
+    pub time: f32,
+    // ODF value at the onset.
+    pub strength: f32,
+    // Threshold the broadband ODF value is compared against.
+    pub threshold: f32,
+    // Bit mask of the configured bands that detected an onset in this frame.
+    // Bit `i` is set for `FrameProcessorConfig::bands[i]`.
+    pub bands: u32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct FrameProcessorConfig {
+    pub mode: OnsetDetectionMode,
+    // Number of samples in each analysis frame, at the analysis rate.
+    pub frame_size: usize,
+    // Number of samples the frames advance by between ODF values, at the
+    // analysis rate.
+    pub hop_size: usize,
+    // Rate of the samples given to the processor.
+    pub sample_rate: f32,
+    // Rate the input is resampled to before framing, so frame sizes,
+    // thresholds and tempo ranges behave the same for any input rate. With
+    // `None` the input is framed at `sample_rate`.
+    pub analysis_rate: Option<f32>,
+    // Window applied to each frame before computing its energy or spectrum.
+    // With `None` the energy ODF uses the raw frame and the spectral modes
+    // use a Hann window, see `window()`.
+    pub window: Option<Window>,
+    pub threshold: ThresholdParams,
+    pub odf: OdfOptions,
+    pub tempo: TempoParams,
+    pub tracking: TrackingParams,
+    // Meter used to number beats within a bar.
+    pub beats_per_bar: usize,
+    // Number of hops analysed before onsets are reported. Thresholds over
+    // the first few hops only cover the values seen so far.
+    pub warmup_frames: usize,
+    // Frequency bands with their own ODF and threshold. When set, the
+    // broadband ODF is the weighted sum of the band ODFs instead of the one
+    // selected by `mode`, and onsets are reported when either the broadband
+    // ODF or any band peaks.
+    pub bands: Vec<Band>,
+}
+
+impl Default for FrameProcessorConfig {
+    fn default() -> Self {
+        Self {
+            mode: OnsetDetectionMode::Energy,
+            frame_size: DEFAULT_FRAME_SIZE,
+            hop_size: DEFAULT_HOP_SIZE,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            analysis_rate: Some(DEFAULT_ANALYSIS_RATE),
+            window: None,
+            threshold: ThresholdParams::default(),
+            odf: OdfOptions::default(),
+            tempo: TempoParams::default(),
+            tracking: TrackingParams::default(),
+            beats_per_bar: DEFAULT_BEATS_PER_BAR,
+            warmup_frames: ThresholdParams::default().m,
+            bands: vec![],
+        }
+    }
+}
+
+impl FrameProcessorConfig {
+    // The configured window, or the default for `mode`.
+    pub fn window(&self) -> Window {
+        match (self.window, self.mode) {
+            (Some(window), _) => window,
+            (None, OnsetDetectionMode::Energy) => Window::Rectangular,
+            (None, _) => Window::Hann,
+        }
+    }
+
+    // Rate at which the frames are sampled.
+    pub fn analysis_rate(&self) -> f32 {
+        self.analysis_rate.unwrap_or(self.sample_rate)
+    }
+
+    // ODF values produced per second.
+    pub fn frame_rate(&self) -> f32 {
+        self.analysis_rate() / self.hop_size as f32
+    }
+
+    pub fn frame_to_seconds(&self, frame: f32) -> f32 {
+        frame / self.frame_rate()
+    }
+
+    // Position in input samples of the start of a hop.
+    pub fn frame_to_sample(&self, frame: usize) -> usize {
+        match self.analysis_rate {
+            Some(rate) if rate != self.sample_rate => {
+                (frame as f64 * self.hop_size as f64 * self.sample_rate as f64 / rate as f64)
+                    .round() as usize
+            }
+            _ => frame * self.hop_size,
+        }
+    }
+
+    // Position in hops of the attack that an ODF peak at `frame` responds
+    // to. The frame analysed at a hop ends with that hop. A tapered window
+    // responds most to the middle of the frame, while the difference between
+    // rectangular frames peaks as the attack crosses from one to the next.
+    pub fn onset_position(&self, frame: usize) -> f32 {
+        let lag = match self.window() {
+            Window::Rectangular => self.frame_size as f32,
+            _ => self.frame_size as f32 / 2.,
+        };
+        (frame as f32 + 1. - lag / self.hop_size as f32).max(0.)
+    }
+
+    // Position in input samples of the attack behind an ODF peak at `frame`,
+    // see `onset_position`.
+    pub fn onset_sample(&self, frame: usize) -> usize {
+        let samples_per_hop =
+            self.hop_size as f64 * self.sample_rate as f64 / self.analysis_rate() as f64;
+        (self.onset_position(frame) as f64 * samples_per_hop).round() as usize
+    }
+
+    // Width in Hz of one bin of the frame spectra.
+    pub fn bin_hz(&self) -> f32 {
+        self.analysis_rate() / self.frame_size.next_power_of_two() as f32
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ProcessorState {
+    WarmingUp { remaining_frames: usize },
+    Ready,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ProcessResult {
+    // State after the samples were processed. No onsets are reported while
+    // warming up.
+    pub state: ProcessorState,
+    pub onsets: Vec<OnsetEvent>,
+    // Tempo changes confirmed in the hops the samples completed.
+    pub tempo_changes: Vec<TempoChange>,
 }
 
 pub struct FrameProcessor {
-    mode: OnsetDetectionMode,
+    config: FrameProcessorConfig,
     frames: (Frame, Frame),
-    history: Vec<f32>,
+    // Spectra of the current frame over the last few hops, newest first.
+    spectra: VecDeque<Vec<Complex>>,
+    // Magnitude spectra of the previous and current frames as of the last
+    // hop, shared by the ODF, the bands and bar tracking.
+    magnitudes: (Vec<f32>, Vec<f32>),
+    history: RingBuffer<f32>,
     threshold: f32,
     highest_peak: f32,
+    // Decaying maximum of the ODF used for normalization.
+    odf_peak: f32,
+    band_detectors: Vec<BandDetector>,
+    tempo_estimator: TempoEstimator,
+    // Tempo estimated over the history as of the last processed hop.
+    tempo: Option<Tempo>,
+    beat_tracker: BeatTracker,
+    tempo_tracker: TempoTracker,
+    // Tempo changes confirmed since the start of the current `process` call.
+    tempo_changes: Vec<TempoChange>,
+    frame_index: usize,
+    bar_tracker: BarTracker,
+    beat: bool,
+    downbeat: bool,
+    // Converts the input to the analysis rate when the two differ.
+    resampler: Option<Resampler>,
+    // Samples that have not yet filled a whole hop.
+    pending: Vec<f32>,
+}
+
+// Post-processing applied to the raw ODF, in field order, before it is
+// stored in the history.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct OdfOptions {
+    // Keep only increases of the ODF. Without it, the energy ODF also treats
+    // drops in energy as onsets, so a short note produces one onset where it
+    // starts and another where it ends. Other modes only produce increases.
+    pub rectify: bool,
+    // Compress the ODF with log(1 + γ × O) for the given γ, so loud passages
+    // dominate less.
+    pub log_compression: Option<f32>,
+    // Divide the ODF by a running maximum that decays with the given time
+    // constant in seconds, so quiet passages after loud ones still produce
+    // peaks.
+    pub normalization: Option<f32>,
+}
+
+impl OdfOptions {
+    // Whether γ and the normalization time constant are positive when set.
+    pub fn is_valid(&self) -> bool {
+        self.log_compression.is_none_or(|gamma| gamma > 0.)
+            && self
+                .normalization
+                .is_none_or(|time_constant| time_constant > 0.)
+    }
+}
+
+// Parameters of the adaptive threshold
+// σn = λ × median(O[nm]) + α × mean(O[nm]) + w × highest peak.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ThresholdParams {
+    pub lambda: f32,
+    pub alpha: f32,
+    // Number of recent ODF values the median and mean are taken over.
+    pub m: usize,
+    // Weight of the highest peak seen so far.
+    pub hp_weight: f32,
 }
 
-struct ThresholdParams {
-    lambda: f32,
-    alpha: f32,
-    m: usize,
-    hp_weight: f32,
+impl Default for ThresholdParams {
+    fn default() -> Self {
+        Self {
+            lambda: 1.0,
+            alpha: 0.7,
+            m: 10,
+            hp_weight: 0.05,
+        }
+    }
+}
+
+impl Default for FrameProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FrameProcessor {
     pub fn new() -> Self {
+        Self::with_mode(OnsetDetectionMode::Energy)
+    }
+
+    pub fn with_mode(mode: OnsetDetectionMode) -> Self {
+        Self::from_config(FrameProcessorConfig {
+            mode,
+            ..FrameProcessorConfig::default()
+        })
+    }
+
+    pub fn from_config(config: FrameProcessorConfig) -> Self {
+        assert!(config.frame_size > 0, "frame size must be positive");
+        assert!(config.hop_size > 0, "hop size must be positive");
+        assert!(config.sample_rate > 0., "sample rate must be positive");
+        assert!(
+            config.analysis_rate() > 0.,
+            "analysis rate must be positive"
+        );
+        assert!(config.bands.len() <= MAX_BANDS, "too many bands");
+        assert!(config.threshold.m > 0, "threshold window must not be empty");
+        assert!(config.odf.is_valid(), "invalid ODF options");
         Self {
-            mode: OnsetDetectionMode::Energy,
-            frames: (Frame::new(), Frame::new()),
-            history: vec![],
+            frames: (
+                Frame::new(config.frame_size, config.window()),
+                Frame::new(config.frame_size, config.window()),
+            ),
+            spectra: VecDeque::new(),
+            magnitudes: (vec![], vec![]),
+            history: RingBuffer::new(history_capacity(&config)),
             threshold: 0f32,
             highest_peak: 0f32,
+            odf_peak: 0f32,
+            band_detectors: config
+                .bands
+                .iter()
+                .map(|&band| BandDetector::new(band, &config.threshold))
+                .collect(),
+            tempo_estimator: TempoEstimator::new(config.frame_rate(), config.tempo),
+            tempo: None,
+            beat_tracker: BeatTracker::new(),
+            tempo_tracker: TempoTracker::new(config.tracking),
+            tempo_changes: vec![],
+            frame_index: 0,
+            bar_tracker: BarTracker::new(config.beats_per_bar),
+            beat: false,
+            downbeat: false,
+            resampler: resampler(&config),
+            pending: Vec::with_capacity(config.hop_size),
+            config,
         }
     }
 
-    fn write(&mut self, buffer: [f32; BUFFER_SIZE]) {
-        let carry_over = self.frames.1.write(buffer);
-        self.frames.0.write(carry_over);
+    pub fn config(&self) -> &FrameProcessorConfig {
+        &self.config
+    }
+
+    pub fn threshold_params(&self) -> ThresholdParams {
+        self.config.threshold
+    }
+
+    // Takes effect from the next processed hop.
+    pub fn set_threshold_params(&mut self, params: ThresholdParams) {
+        assert!(params.m > 0, "threshold window must not be empty");
+        self.config.threshold = params;
+        self.history.set_capacity(history_capacity(&self.config));
+        for detector in &mut self.band_detectors {
+            detector.set_threshold_params(&params);
+        }
+    }
+
+    pub fn tempo_params(&self) -> TempoParams {
+        self.config.tempo
+    }
+
+    pub fn set_tempo_params(&mut self, params: TempoParams) {
+        self.config.tempo = params;
+        self.tempo_estimator = TempoEstimator::new(self.config.frame_rate(), params);
+        self.history.set_capacity(history_capacity(&self.config));
+        self.update_tempo();
+    }
+
+    // Takes effect from the next processed hop.
+    pub fn set_odf_options(&mut self, options: OdfOptions) {
+        assert!(options.is_valid(), "invalid ODF options");
+        self.config.odf = options;
+    }
+
+    // Takes effect from the next processed hop.
+    pub fn set_window(&mut self, window: Window) {
+        self.config.window = Some(window);
+        self.frames.0.set_window(window);
+        self.frames.1.set_window(window);
+        self.spectra.clear();
+    }
+
+    fn write(&mut self, hop: &[f32]) {
+        let carry_over = self.frames.1.write(hop);
+        self.frames.0.write(&carry_over);
     }
 
     fn update_history(&mut self, value: f32) {
-        self.history.insert(0, value);
+        self.history.push(value);
+    }
+
+    // Re-estimates the tempo over the recent history. Done once per hop so
+    // reading `tempo` stays cheap.
+    fn update_tempo(&mut self) {
+        let horizon = tempo_horizon(&self.config);
+        self.tempo = self.tempo_estimator.estimate(&self.history.recent(horizon));
     }
 
     fn calculate_threshold(&mut self) -> f32 {
-        // σn = λ × median(O[nm]) + α × mean(O[nm]) + N
-        let params = ThresholdParams {
-            lambda: 1.0,
-            alpha: 0.7,
-            m: 10,
-            hp_weight: 0.05,
-        };
-        let ThresholdParams {
-            lambda,
-            alpha,
-            m,
-            hp_weight,
-        } = params;
-        let weighted_highest_peak = self.highest_peak * hp_weight;
-        let prev_values = &self.history[0..m];
         self.threshold =
-            lambda * median(prev_values) + alpha * mean(prev_values) + weighted_highest_peak;
+            adaptive_threshold(&self.history, self.highest_peak, &self.config.threshold);
         self.threshold
     }
 
     fn check_for_previous_onset(&mut self) -> bool {
-        let (curr, prev, prev_prev) = match self.history[0..3] {
-            [a, b, c] => (a, b, c),
-            _ => (0., 0., 0.),
-        };
-        if prev > curr && prev > prev_prev {
-            if prev > self.threshold {
-                self.highest_peak = match (prev > self.highest_peak) {
-                    true => prev,
+        match previous_peak(&self.history, self.threshold) {
+            Some(peak) => {
+                self.highest_peak = match peak > self.highest_peak {
+                    true => peak,
                     false => self.highest_peak,
                 };
-                return true;
+                true
             }
+            None => false,
         }
-        false
     }
 
-    pub fn process(&mut self, buffer: [f32; BUFFER_SIZE]) -> bool {
-        self.write(buffer);
+    pub fn state(&self) -> ProcessorState {
+        match self.config.warmup_frames.checked_sub(self.frame_index) {
+            Some(remaining_frames) if remaining_frames > 0 => {
+                ProcessorState::WarmingUp { remaining_frames }
+            }
+            _ => ProcessorState::Ready,
+        }
+    }
+
+    // Accepts any number of samples and returns the onsets detected in the
+    // hops they complete. Because an onset is only confirmed one hop later,
+    // and resampling holds back a few samples, it may lie slightly before
+    // the start of `samples`.
+    pub fn process(&mut self, samples: &[f32]) -> ProcessResult {
+        let resampled = self
+            .resampler
+            .as_mut()
+            .map(|resampler| resampler.process(samples));
+        let samples = resampled.as_deref().unwrap_or(samples);
+        let mut onsets = vec![];
+        let mut beat = false;
+        let mut downbeat = false;
+        for &sample in samples {
+            self.pending.push(sample);
+            if self.pending.len() == self.config.hop_size {
+                let hop = std::mem::take(&mut self.pending);
+                if let Some(onset) = self.process_hop(&hop) {
+                    onsets.push(onset);
+                }
+                self.pending = hop;
+                self.pending.clear();
+                beat |= self.beat;
+                downbeat |= self.downbeat;
+            }
+        }
+        self.beat = beat;
+        self.downbeat = downbeat;
+        ProcessResult {
+            state: self.state(),
+            onsets,
+            tempo_changes: std::mem::take(&mut self.tempo_changes),
+        }
+    }
 
+    fn detection_function(&self) -> f32 {
         let (prev, curr) = &self.frames;
-        let odf = (curr.energy() - prev.energy()).abs();
+        let (prev_magnitudes, magnitudes) = &self.magnitudes;
+        let spectrum = |hops: usize| self.spectra.get(hops).map_or(&[][..], |s| &s[..]);
+        match self.config.mode {
+            OnsetDetectionMode::Energy => curr.energy() - prev.energy(),
+            OnsetDetectionMode::SpectralDifference => spectral_flux(prev_magnitudes, magnitudes),
+            OnsetDetectionMode::HighFrequencyContent => (high_frequency_content(magnitudes)
+                - high_frequency_content(prev_magnitudes))
+            .max(0.),
+            OnsetDetectionMode::ComplexDomain => {
+                complex_domain(spectrum(0), spectrum(1), spectrum(2))
+            }
+            OnsetDetectionMode::PhaseDeviation => {
+                phase_deviation(spectrum(0), spectrum(1), spectrum(2))
+            }
+        }
+    }
 
+    // Applies the configured `OdfOptions` to a raw ODF value.
+    fn post_process(&mut self, odf: f32) -> f32 {
+        let OdfOptions {
+            rectify,
+            log_compression,
+            normalization,
+        } = self.config.odf;
+        let mut odf = match rectify {
+            true => odf.max(0.),
+            false => odf.abs(),
+        };
+        if let Some(gamma) = log_compression {
+            odf = (gamma * odf).ln_1p();
+        }
+        if let Some(time_constant) = normalization {
+            let decay = (-1. / (time_constant * self.config.frame_rate())).exp();
+            self.odf_peak = odf.max(self.odf_peak * decay);
+            odf = match self.odf_peak > 0. {
+                true => odf / self.odf_peak,
+                false => 0.,
+            };
+        }
+        odf
+    }
+
+    // Computes the current frame's spectrum, the one FFT per hop. The
+    // previous frame's spectrum is the current one from a few hops back when
+    // the frames line up with whole hops, and is only computed otherwise.
+    fn update_spectra(&mut self) {
+        let spectrum = self.frames.1.complex_spectrum();
+        let hops = previous_frame_hops(&self.config);
+        let magnitudes = magnitudes(&spectrum);
+        let prev_magnitudes = match hops.and_then(|hops| self.spectra.get(hops - 1)) {
+            Some(prev) => self::magnitudes(prev),
+            None => self::magnitudes(&self.frames.0.complex_spectrum()),
+        };
+        self.spectra.push_front(spectrum);
+        self.spectra.truncate(hops.unwrap_or(0).max(3));
+        self.magnitudes = (prev_magnitudes, magnitudes);
+    }
+
+    // Advances the frames by `hop` and returns the post-processed broadband
+    // ODF together with the raw ODF of each band.
+    fn analyse_hop(&mut self, hop: &[f32]) -> (f32, Vec<f32>) {
+        self.write(hop);
+        self.update_spectra();
+
+        let band_odfs = self.band_odfs();
+        let odf = match band_odfs.is_empty() {
+            true => self.detection_function(),
+            false => band_odfs
+                .iter()
+                .zip(self.band_detectors.iter())
+                .map(|(odf, detector)| odf * detector.band().weight)
+                .sum(),
+        };
+        (self.post_process(odf), band_odfs)
+    }
+
+    // Computes the broadband ODF of a whole signal, one value per hop,
+    // without peak picking or beat tracking. The signal is resampled to the
+    // analysis rate first and a trailing partial hop is zero-padded. Runs on
+    // a fresh processor with the same configuration, so this one's state is
+    // left alone.
+    pub fn odf_curve(&self, signal: &[f32]) -> Vec<f32> {
+        let mut processor = FrameProcessor::from_config(self.config.clone());
+        let hop_size = self.config.hop_size;
+        let resampled = processor.resampler.as_mut().map(|resampler| {
+            let mut resampled = resampler.process(signal);
+            resampled.extend(resampler.flush());
+            resampled
+        });
+        resampled
+            .as_deref()
+            .unwrap_or(signal)
+            .chunks(hop_size)
+            .map(|chunk| {
+                let mut hop = chunk.to_vec();
+                hop.resize(hop_size, 0.);
+                processor.analyse_hop(&hop).0
+            })
+            .collect()
+    }
+
+    fn process_hop(&mut self, hop: &[f32]) -> Option<OnsetEvent> {
+        let (odf, band_odfs) = self.analyse_hop(hop);
         self.update_history(odf);
+        self.update_tempo();
         self.calculate_threshold();
-        self.check_for_previous_onset()
+        self.frame_index += 1;
+        let ready = self.state() == ProcessorState::Ready;
+        let onset = ready && self.check_for_previous_onset();
+        let params = self.config.threshold;
+        let mut bands = 0;
+        for (i, (detector, &band_odf)) in self
+            .band_detectors
+            .iter_mut()
+            .zip(band_odfs.iter())
+            .enumerate()
+        {
+            if detector.update(band_odf, &params, ready) {
+                bands |= 1 << i;
+            }
+        }
+        self.track_beats(onset);
+        self.track_tempo();
+        match onset || bands != 0 {
+            true => Some(self.previous_onset_event(bands)),
+            false => None,
+        }
+    }
+
+    // Spectral flux of each configured band between the two frames.
+    fn band_odfs(&self) -> Vec<f32> {
+        if self.band_detectors.is_empty() {
+            return vec![];
+        }
+        let (prev, curr) = &self.magnitudes;
+        let bin_hz = self.config.bin_hz();
+        self.band_detectors
+            .iter()
+            .map(|detector| detector.odf(prev, curr, bin_hz))
+            .collect()
+    }
+
+    // Describes an onset found one frame back, by the broadband ODF or by the
+    // bands in `bands`.
+    fn previous_onset_event(&self, bands: u32) -> OnsetEvent {
+        let frame = self.frame_index - 2;
+        OnsetEvent {
+            frame,
+            sample: self.config.onset_sample(frame),
+            time: self
+                .config
+                .frame_to_seconds(self.config.onset_position(frame)),
+            strength: self.history.get(1).unwrap_or(0.),
+            threshold: self.threshold,
+            bands,
+        }
+    }
+
+    // Feeds a tempo estimate over the most recent history to the tempo
+    // tracker once every tracking step.
+    fn track_tempo(&mut self) {
+        if !self.frame_index.is_multiple_of(tracking_step(&self.config)) {
+            return;
+        }
+        let estimate = match self.tempo() {
+            Some(estimate) => estimate,
+            None => return,
+        };
+        if let Some((from_bpm, to_bpm)) = self.tempo_tracker.update(estimate) {
+            let frame = self.frame_index - 1;
+            self.tempo_changes.push(TempoChange {
+                frame,
+                time: self.config.frame_to_seconds(frame as f32),
+                from_bpm,
+                to_bpm,
+            });
+        }
+    }
+
+    fn track_beats(&mut self, onset: bool) {
+        if let Some(tempo) = self.tempo() {
+            self.beat_tracker
+                .set_period(60. * self.config.frame_rate() / tempo.bpm);
+        }
+        // `frame_index` already counts the hop just processed.
+        let frame = self.frame_index - 1;
+        if onset {
+            // The onset belongs to the previous frame, see
+            // `check_for_previous_onset`.
+            self.beat_tracker
+                .onset(frame as f32 - 1., self.history.get(1).unwrap_or(0.));
+        }
+        self.beat = self.beat_tracker.advance(frame);
+        self.track_bars(frame);
+    }
+
+    // Attributes the current frame's low-frequency accent to the nearest
+    // beat and numbers the beats within the bar.
+    fn track_bars(&mut self, frame: usize) {
+        if self.beat {
+            self.bar_tracker.beat();
+        }
+        self.downbeat = self.beat && self.bar_tracker.bar_position() == Some(1);
+        let (period, next_beat) = match (self.beat_tracker.period(), self.beat_tracker.next_beat())
+        {
+            (Some(period), Some(next_beat)) => (period, next_beat),
+            _ => return,
+        };
+        let (prev, curr) = &self.magnitudes;
+        let accent = Band::new(0., ACCENT_BAND_HZ, 1.).flux(prev, curr, self.config.bin_hz());
+        self.bar_tracker
+            .accent(accent, next_beat - frame as f32 <= period / 2.);
+    }
+
+    // Whether a beat was predicted within the hops completed by the last call
+    // to `process`.
+    pub fn beat(&self) -> bool {
+        self.beat
+    }
+
+    // Whether one of the beats predicted during the last call to `process`
+    // was the first of a bar.
+    pub fn downbeat(&self) -> bool {
+        self.downbeat
+    }
+
+    // Position of the most recent beat in its bar, from 1 for the downbeat
+    // to `FrameProcessorConfig::beats_per_bar`.
+    pub fn bar_position(&self) -> Option<usize> {
+        self.bar_tracker.bar_position()
+    }
+
+    // Takes effect from the next beat; the downbeat is estimated afresh.
+    pub fn set_beats_per_bar(&mut self, beats_per_bar: usize) {
+        self.config.beats_per_bar = beats_per_bar;
+        self.bar_tracker = BarTracker::new(beats_per_bar);
+    }
+
+    // Sample offset, counted from the first processed sample, at which the
+    // next beat is expected.
+    pub fn next_beat_sample(&self) -> Option<usize> {
+        let samples_per_frame =
+            self.config.hop_size as f32 * self.config.sample_rate / self.config.analysis_rate();
+        self.beat_tracker
+            .next_beat()
+            .map(|frame| (frame * samples_per_frame).round() as usize)
+    }
+
+    // Time in seconds, counted from the first processed sample, at which the
+    // next beat is expected.
+    pub fn next_beat_time(&self) -> Option<f32> {
+        self.beat_tracker
+            .next_beat()
+            .map(|frame| self.config.frame_to_seconds(frame))
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    // Most recent onset detection function value.
+    pub fn odf(&self) -> f32 {
+        self.history.first().unwrap_or(0.)
+    }
+
+    // Tempo smoothed over successive estimates, see `TrackingParams`.
+    pub fn smoothed_tempo(&self) -> Option<Tempo> {
+        self.tempo_tracker.tempo()
+    }
+
+    // Tempos of the rows of `tempogram_column`.
+    pub fn tempogram_bpms(&self) -> Vec<f32> {
+        self.tempo_estimator.tempogram_bpms()
+    }
+
+    // Tempogram column over the same history as `tempo`, for building a
+    // tempogram incrementally. `None` until enough history is available.
+    pub fn tempogram_column(&self) -> Option<Vec<f32>> {
+        let horizon = tempo_horizon(&self.config);
+        self.tempo_estimator
+            .tempogram_column(&self.history.recent(horizon))
+    }
+
+    // Estimates the tempo from the recent ODF history. Returns `None` until
+    // enough audio has been processed.
+    pub fn tempo(&self) -> Option<Tempo> {
+        self.tempo
+    }
+}
+
+// σn = λ × median(O[nm]) + α × mean(O[nm]) + w × highest peak, over the
+// most recent values of `history`.
+pub fn adaptive_threshold(
+    history: &RingBuffer<f32>,
+    highest_peak: f32,
+    params: &ThresholdParams,
+) -> f32 {
+    let ThresholdParams {
+        lambda,
+        alpha,
+        m,
+        hp_weight,
+    } = *params;
+    let weighted_highest_peak = highest_peak * hp_weight;
+    let prev_values = history.recent(m);
+    lambda * median(&prev_values) + alpha * mean(&prev_values) + weighted_highest_peak
+}
+
+// Returns the ODF value one frame back if it is a local maximum above
+// `threshold`.
+pub fn previous_peak(history: &RingBuffer<f32>, threshold: f32) -> Option<f32> {
+    let (curr, prev, prev_prev) = match history.recent(3)[..] {
+        [a, b, c] => (a, b, c),
+        _ => (0., 0., 0.),
+    };
+    match prev > curr && prev > prev_prev && prev > threshold {
+        true => Some(prev),
+        false => None,
+    }
+}
+
+// Resampler to the analysis rate, if it differs from the input rate.
+fn resampler(config: &FrameProcessorConfig) -> Option<Resampler> {
+    match config.analysis_rate {
+        Some(rate) if rate != config.sample_rate => Some(Resampler::new(config.sample_rate, rate)),
+        _ => None,
+    }
+}
+
+// Number of ODF frames between successive tempo tracking updates.
+pub fn tracking_step(config: &FrameProcessorConfig) -> usize {
+    (config.tracking.step * config.frame_rate()).round().max(1.) as usize
+}
+
+// Number of ODF values used for tempo estimation.
+pub fn tempo_horizon(config: &FrameProcessorConfig) -> usize {
+    let seconds = TEMPO_HORIZON_SECONDS.max(3. * 60. / config.tempo.min_bpm);
+    (seconds * config.frame_rate()).ceil() as usize
+}
+
+// The ODF history only needs to cover the threshold window, the three values
+// peak picking looks at and the tempo horizon.
+fn history_capacity(config: &FrameProcessorConfig) -> usize {
+    config.threshold.m.max(3).max(tempo_horizon(config))
+}
+
+// Sum over bins of the distance between the current spectrum and the one
+// predicted by extrapolating magnitude and phase from the previous two.
+fn complex_domain(curr: &[Complex], prev: &[Complex], prev_prev: &[Complex]) -> f32 {
+    curr.iter()
+        .zip(prev.iter().zip(prev_prev.iter()))
+        .map(|(&c, (&p, &pp))| {
+            let predicted = Complex::from_polar(p.norm(), 2. * p.arg() - pp.arg());
+            (c - predicted).norm()
+        })
+        .sum()
+}
+
+// Mean over bins of the absolute second-order phase difference, wrapped to
+// [-π, π].
+fn phase_deviation(curr: &[Complex], prev: &[Complex], prev_prev: &[Complex]) -> f32 {
+    let deviations: Vec<f32> = curr
+        .iter()
+        .zip(prev.iter().zip(prev_prev.iter()))
+        .map(|(c, (p, pp))| princarg(c.arg() - 2. * p.arg() + pp.arg()).abs())
+        .collect();
+    match deviations.is_empty() {
+        true => 0.,
+        false => mean(&deviations),
+    }
+}
+
+// Maps a phase to the range [-π, π].
+fn princarg(phase: f32) -> f32 {
+    phase - 2. * PI * ((phase + PI) / (2. * PI)).floor()
+}
+
+// Sum of the positive differences between two magnitude spectra.
+fn spectral_flux(prev: &[f32], curr: &[f32]) -> f32 {
+    prev.iter()
+        .zip(curr.iter())
+        .map(|(p, c)| (c - p).max(0.))
+        .sum()
+}
+
+// Spectral energy with each bin weighted by its index, normalized by the
+// number of bins.
+fn high_frequency_content(magnitudes: &[f32]) -> f32 {
+    let weighted: f32 = magnitudes
+        .iter()
+        .enumerate()
+        .map(|(k, magnitude)| k as f32 * magnitude * magnitude)
+        .sum();
+    weighted / magnitudes.len() as f32
+}
+
+fn magnitudes(spectrum: &[Complex]) -> Vec<f32> {
+    spectrum.iter().map(|bin| bin.norm()).collect()
+}
+
+// Number of hops after which the current frame has become the previous one,
+// or `None` when the frames do not line up with whole hops.
+fn previous_frame_hops(config: &FrameProcessorConfig) -> Option<usize> {
+    let (frame_size, hop_size) = (config.frame_size, config.hop_size);
+    match (hop_size >= frame_size, frame_size.is_multiple_of(hop_size)) {
+        (true, _) => Some(1),
+        (false, true) => Some(frame_size / hop_size),
+        (false, false) => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::pulse_train;
+
+    const BUFFER_SIZE: usize = DEFAULT_HOP_SIZE;
+    const FRAME_SIZE: usize = DEFAULT_FRAME_SIZE;
+
+    fn spectrum(frame: &Frame) -> Vec<f32> {
+        magnitudes(&frame.complex_spectrum())
+    }
+
+    #[test]
+    fn test_onset_placement() {
+        let attack = 20100;
+        // A decaying 440 Hz note.
+        let signal: Vec<f32> = (0..DEFAULT_SAMPLE_RATE as usize)
+            .map(|i| match i >= attack {
+                true => {
+                    let t = (i - attack) as f32 / DEFAULT_SAMPLE_RATE;
+                    0.5 * (-t * 10.).exp() * (2. * PI * 440. * t).sin()
+                }
+                false => 0.,
+            })
+            .collect();
+        for mode in [
+            OnsetDetectionMode::Energy,
+            OnsetDetectionMode::SpectralDifference,
+        ] {
+            let onsets = FrameProcessor::with_mode(mode).process(&signal).onsets;
+            assert_eq!(onsets.len(), 1, "{:?}", onsets);
+            let error = onsets[0].sample as f32 - attack as f32;
+            assert!(error.abs() < BUFFER_SIZE as f32, "{:?} {}", mode, error);
+        }
+    }
 
     #[test]
     fn test_frame_write() {
-        let mut frame = Frame::new();
+        let mut frame = Frame::new(FRAME_SIZE, Window::Hann);
         let buffer = frame.buffer();
         assert_eq!(buffer.len(), FRAME_SIZE);
         assert_eq!(buffer[0], 0.);
-        frame.write([1.; BUFFER_SIZE]);
+        frame.write(&[1.; BUFFER_SIZE]);
         let buffer = frame.buffer();
         assert_eq!(buffer[0], 0.);
         assert_eq!(buffer[FRAME_SIZE - 1], 1.);
@@ -181,10 +982,383 @@ mod tests {
     fn test_frame_processor_write() {
         let mut processor = FrameProcessor::new();
         for i in 0..10 {
-            processor.write([i as f32; BUFFER_SIZE]);
+            processor.write(&[i as f32; BUFFER_SIZE]);
         }
-        assert_eq!(processor.mode, OnsetDetectionMode::Energy);
+        assert_eq!(processor.config.mode, OnsetDetectionMode::Energy);
         assert_eq!(processor.frames.0.buffer()[0], 2.);
         assert_eq!(processor.frames.1.buffer()[FRAME_SIZE - 1], 9.);
     }
+
+    #[test]
+    fn test_spectral_flux() {
+        let silence = spectrum(&Frame::new(FRAME_SIZE, Window::Hann));
+        let mut frame = Frame::new(FRAME_SIZE, Window::Hann);
+        assert_eq!(spectral_flux(&silence, &spectrum(&frame)), 0.);
+        frame.write(&[1.; BUFFER_SIZE]);
+        assert!(spectral_flux(&silence, &spectrum(&frame)) > 0.);
+        // Energy drops do not contribute to the flux.
+        assert_eq!(spectral_flux(&spectrum(&frame), &silence), 0.);
+    }
+
+    #[test]
+    fn test_frame_processor_with_mode() {
+        let processor = FrameProcessor::with_mode(OnsetDetectionMode::SpectralDifference);
+        assert_eq!(
+            processor.config.mode,
+            OnsetDetectionMode::SpectralDifference
+        );
+    }
+
+    #[test]
+    fn test_frame_processor_tempo() {
+        let mut processor = FrameProcessor::new();
+        assert_eq!(processor.tempo(), None);
+        // A peak every 43 buffers is close to 120 BPM at 44.1 kHz.
+        for odf in pulse_train(600, 43) {
+            processor.update_history(odf);
+        }
+        assert_eq!(processor.tempo(), None);
+        processor.update_tempo();
+        let tempo = processor.tempo().unwrap();
+        assert!((tempo.bpm - 120.).abs() < 2., "{}", tempo.bpm);
+    }
+
+    #[test]
+    fn test_frame_processor_track_beats() {
+        let mut processor = FrameProcessor::new();
+        for odf in pulse_train(600, 43) {
+            processor.update_history(odf);
+        }
+        processor.update_tempo();
+        assert_eq!(processor.next_beat_sample(), None);
+        processor.frame_index = 602;
+        processor.track_beats(true);
+        let next_beat = processor.next_beat_sample().unwrap();
+        assert!((next_beat as f32 - 643. * BUFFER_SIZE as f32).abs() < BUFFER_SIZE as f32);
+        assert!(!processor.beat());
+    }
+
+    #[test]
+    fn test_frame_processor_downbeats() {
+        let sample_rate = DEFAULT_SAMPLE_RATE;
+        let beat_samples = (sample_rate / 2.) as usize;
+        // Hi-hat clicks on every beat at 120 BPM, with a kick on the first
+        // beat of each four-beat bar.
+        let signal: Vec<f32> = (0..beat_samples * 32)
+            .map(|i| {
+                let t = (i % beat_samples) as f32 / sample_rate;
+                let hat = (-t * 60.).exp() * (2. * PI * 6000. * t).sin();
+                let kick = match (i / beat_samples) % 4 {
+                    0 => (-t * 20.).exp() * (2. * PI * 60. * t).sin(),
+                    _ => 0.,
+                };
+                hat + kick
+            })
+            .collect();
+
+        let mut processor = FrameProcessor::new();
+        let mut downbeats = vec![];
+        for (i, block) in signal.chunks(BUFFER_SIZE).enumerate() {
+            processor.process(block);
+            if processor.downbeat() {
+                downbeats.push(i as f32 * BUFFER_SIZE as f32 / sample_rate);
+            }
+        }
+        // Once accents have accumulated, downbeats fall on the kicks, which
+        // are two seconds apart.
+        let late: Vec<f32> = downbeats.into_iter().filter(|&t| t > 7.).collect();
+        assert!(late.len() >= 4, "{:?}", late);
+        for time in late {
+            let offset = (time + 1.) % 2. - 1.;
+            assert!(offset.abs() < 0.1, "{}", time);
+        }
+    }
+
+    #[test]
+    fn test_frame_processor_process_arbitrary_lengths() {
+        let mut processor = FrameProcessor::from_config(FrameProcessorConfig {
+            warmup_frames: 0,
+            ..FrameProcessorConfig::default()
+        });
+        assert!(processor.process(&[0.; 100]).onsets.is_empty());
+        assert_eq!(processor.frame_index, 0);
+        assert!(processor.process(&[0.; 1500]).onsets.is_empty());
+        assert_eq!(processor.frame_index, 3);
+        assert_eq!(processor.pending.len(), 1600 - 3 * BUFFER_SIZE);
+        processor.process(&[0.; 4 * BUFFER_SIZE - 1600]);
+
+        // A crescendo over four buffers gives the energy ODF a single peak
+        // on the loudest one.
+        let mut samples = vec![0.; 8 * BUFFER_SIZE];
+        for (i, buffer) in samples.chunks_mut(BUFFER_SIZE).take(4).enumerate() {
+            for sample in buffer {
+                *sample = ((i + 1) as f32).sqrt();
+            }
+        }
+        let onsets = processor.process(&samples).onsets;
+        assert_eq!(onsets.len(), 1);
+        let onset = onsets[0];
+        assert_eq!(onset.frame, 7);
+        // The peak is placed where the crescendo starts.
+        assert_eq!(onset.sample, 4 * BUFFER_SIZE);
+        assert_eq!(onset.time, 4. * BUFFER_SIZE as f32 / DEFAULT_SAMPLE_RATE);
+        assert_eq!(onset.strength, 10. * BUFFER_SIZE as f32);
+        assert!(onset.threshold < onset.strength);
+    }
+
+    #[test]
+    fn test_frame_processor_config() {
+        let config = FrameProcessorConfig {
+            frame_size: 1024,
+            hop_size: 256,
+            sample_rate: 16000.,
+            analysis_rate: None,
+            ..FrameProcessorConfig::default()
+        };
+        assert_eq!(config.frame_rate(), 62.5);
+        assert_eq!(config.frame_to_seconds(125.), 2.);
+        assert_eq!(config.frame_to_sample(3), 768);
+
+        let mut processor = FrameProcessor::from_config(config);
+        processor.write(&[1.; 256]);
+        assert_eq!(processor.frames.1.buffer().len(), 1024);
+        assert_eq!(processor.frames.1.buffer()[1023], 1.);
+        assert_eq!(processor.frames.1.buffer()[767], 0.);
+    }
+
+    #[test]
+    fn test_resampled_input() {
+        let config = FrameProcessorConfig {
+            sample_rate: 48000.,
+            ..FrameProcessorConfig::default()
+        };
+        assert_eq!(config.frame_rate(), DEFAULT_ANALYSIS_RATE / 512.);
+        assert_eq!(config.frame_to_sample(441), 480 * 512);
+
+        // A second of audio at 48 kHz fills as many hops as a second at the
+        // analysis rate.
+        let mut processor = FrameProcessor::from_config(config);
+        processor.process(&vec![0.; 48000]);
+        assert_eq!(processor.frame_index, 44100 / 512);
+        let odf = processor.odf_curve(&vec![0.; 48000]);
+        assert_eq!(odf.len(), 44100usize.div_ceil(512));
+        // The streaming processor is not advanced.
+        assert_eq!(processor.frame_index, 44100 / 512);
+    }
+
+    #[test]
+    fn test_set_threshold_params() {
+        let mut processor = FrameProcessor::new();
+        assert_eq!(processor.threshold_params(), ThresholdParams::default());
+        for i in 0..10 {
+            processor.update_history(i as f32);
+        }
+        processor.set_threshold_params(ThresholdParams {
+            lambda: 0.,
+            alpha: 1.,
+            m: 4,
+            hp_weight: 0.,
+        });
+        processor.calculate_threshold();
+        assert_eq!(processor.threshold(), 7.5);
+
+        processor.set_threshold_params(ThresholdParams {
+            lambda: 2.,
+            m: 3,
+            ..ThresholdParams::default()
+        });
+        processor.calculate_threshold();
+        assert_eq!(processor.threshold(), 2. * 8. + 0.7 * 8.);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut processor = FrameProcessor::new();
+        let capacity = history_capacity(processor.config());
+        assert_eq!(capacity, 517);
+        for i in 0..2 * capacity {
+            processor.update_history(i as f32);
+        }
+        assert_eq!(processor.history.recent(usize::MAX).len(), capacity);
+        assert_eq!(processor.odf(), (2 * capacity - 1) as f32);
+
+        // Slow tempos need three of their periods.
+        processor.set_tempo_params(TempoParams {
+            min_bpm: 20.,
+            ..TempoParams::default()
+        });
+        assert_eq!(history_capacity(processor.config()), 776);
+    }
+
+    #[test]
+    fn test_warmup() {
+        let mut processor = FrameProcessor::new();
+        assert_eq!(
+            processor.state(),
+            ProcessorState::WarmingUp {
+                remaining_frames: 10
+            }
+        );
+        let result = processor.process(&[1.; 9 * BUFFER_SIZE]);
+        assert_eq!(
+            result.state,
+            ProcessorState::WarmingUp {
+                remaining_frames: 1
+            }
+        );
+        assert!(result.onsets.is_empty());
+        assert!(processor.threshold() > 0.);
+        let result = processor.process(&[1.; BUFFER_SIZE]);
+        assert_eq!(result.state, ProcessorState::Ready);
+    }
+
+    #[test]
+    fn test_previous_frame_spectrum() {
+        for hop_size in [BUFFER_SIZE, 300] {
+            let mut processor = FrameProcessor::from_config(FrameProcessorConfig {
+                mode: OnsetDetectionMode::SpectralDifference,
+                hop_size,
+                ..FrameProcessorConfig::default()
+            });
+            for i in 0..8 {
+                let hop: Vec<f32> = (0..hop_size)
+                    .map(|j| ((i * hop_size + j) as f32 * 0.05).sin())
+                    .collect();
+                processor.analyse_hop(&hop);
+                let expected = spectrum(&processor.frames.0);
+                for (a, b) in processor.magnitudes.0.iter().zip(expected.iter()) {
+                    assert!((a - b).abs() < 1e-3, "{} {}", a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_high_frequency_content() {
+        let sine = |cycles: f32| {
+            let mut frame = Frame::new(FRAME_SIZE, Window::Hann);
+            let samples: Vec<f32> = (0..FRAME_SIZE)
+                .map(|i| (2. * PI * cycles * i as f32 / FRAME_SIZE as f32).sin())
+                .collect();
+            frame.write(&samples);
+            frame
+        };
+        let hfc = |frame: Frame| high_frequency_content(&spectrum(&frame));
+        assert_eq!(hfc(Frame::new(FRAME_SIZE, Window::Hann)), 0.);
+        // Equal amplitude, but the higher tone carries more weight.
+        assert!(hfc(sine(400.)) > 10. * hfc(sine(20.)));
+    }
+
+    #[test]
+    fn test_complex_domain() {
+        let bins = |magnitude: f32, phase: f32| vec![Complex::from_polar(magnitude, phase); 4];
+        // A stationary partial advances its phase by a constant step.
+        let stationary = complex_domain(&bins(1., 1.), &bins(1., 0.5), &bins(1., 0.));
+        assert!(stationary < 1e-5, "{}", stationary);
+        // A louder note deviates from the predicted magnitude.
+        let onset = complex_domain(&bins(2., 1.), &bins(1., 0.5), &bins(1., 0.));
+        assert!((onset - 4.).abs() < 1e-5, "{}", onset);
+        assert_eq!(complex_domain(&bins(1., 0.), &[], &[]), 0.);
+    }
+
+    #[test]
+    fn test_phase_deviation() {
+        let bins = |phase: f32| vec![Complex::from_polar(1., phase); 4];
+        let stationary = phase_deviation(&bins(3.), &bins(2.), &bins(1.));
+        assert!(stationary < 1e-5, "{}", stationary);
+        let onset = phase_deviation(&bins(2.), &bins(2.), &bins(1.));
+        assert!((onset - 1.).abs() < 1e-5, "{}", onset);
+        assert_eq!(phase_deviation(&bins(0.), &[], &[]), 0.);
+    }
+
+    #[test]
+    fn test_princarg() {
+        assert!((princarg(3. * PI / 2.) + PI / 2.).abs() < 1e-5);
+        assert!((princarg(-3. * PI / 2.) - PI / 2.).abs() < 1e-5);
+        assert_eq!(princarg(1.), 1.);
+    }
+
+    #[test]
+    fn test_band_onsets() {
+        let mut processor = FrameProcessor::from_config(FrameProcessorConfig {
+            warmup_frames: 0,
+            bands: vec![Band::new(20., 200., 1.), Band::new(4000., 16000., 1.)],
+            ..FrameProcessorConfig::default()
+        });
+        processor.process(&[0.; 4 * BUFFER_SIZE]);
+        // A swelling 100 Hz tone.
+        let samples: Vec<f32> = (0..8 * BUFFER_SIZE)
+            .map(|i| {
+                let amplitude = ((i / BUFFER_SIZE).min(3) + 1) as f32;
+                amplitude * (2. * PI * 100. * i as f32 / DEFAULT_SAMPLE_RATE).sin()
+            })
+            .collect();
+        let onsets = processor.process(&samples).onsets;
+        let low_band = onsets.iter().find(|onset| onset.bands & 1 != 0).unwrap();
+        assert!(low_band.frame >= 4);
+        // The tone itself does not reach the 4-16 kHz band.
+        assert_eq!(low_band.bands & 2, 0);
+    }
+
+    #[test]
+    fn test_frame_window() {
+        let mut frame = Frame::new(FRAME_SIZE, Window::Rectangular);
+        frame.write(&[1.; FRAME_SIZE]);
+        assert_eq!(frame.energy(), FRAME_SIZE as f32);
+        frame.set_window(Window::Hann);
+        // The mean of the squared Hann window is 3/8.
+        assert!((frame.energy() - 0.375 * FRAME_SIZE as f32).abs() < 1e-2);
+        assert_eq!(frame.buffer()[0], 1.);
+
+        // Energy uses the raw frame unless a window is chosen.
+        let config = FrameProcessorConfig::default();
+        assert_eq!(config.window(), Window::Rectangular);
+        let config = FrameProcessorConfig {
+            mode: OnsetDetectionMode::SpectralDifference,
+            ..config
+        };
+        assert_eq!(config.window(), Window::Hann);
+    }
+
+    #[test]
+    fn test_odf_post_processing() {
+        let mut processor = FrameProcessor::new();
+        assert_eq!(processor.post_process(-2.), 2.);
+
+        processor.set_odf_options(OdfOptions {
+            rectify: true,
+            ..OdfOptions::default()
+        });
+        assert_eq!(processor.post_process(-2.), 0.);
+        assert_eq!(processor.post_process(2.), 2.);
+
+        processor.set_odf_options(OdfOptions {
+            log_compression: Some(1.),
+            ..OdfOptions::default()
+        });
+        assert_eq!(processor.post_process(std::f32::consts::E - 1.), 1.);
+
+        processor.set_odf_options(OdfOptions {
+            normalization: Some(1.),
+            ..OdfOptions::default()
+        });
+        assert_eq!(processor.post_process(0.), 0.);
+        assert_eq!(processor.post_process(100.), 1.);
+        // The running maximum decays slightly with every hop.
+        let value = processor.post_process(50.);
+        assert!(value > 0.5 && value < 0.51, "{}", value);
+        // A second later the loud peak has decayed to about a third.
+        for _ in 0..DEFAULT_SAMPLE_RATE as usize / BUFFER_SIZE {
+            processor.post_process(0.);
+        }
+        assert!(processor.post_process(10.) > 0.25);
+
+        let invalid = |log_compression, normalization| OdfOptions {
+            log_compression,
+            normalization,
+            ..OdfOptions::default()
+        };
+        assert!(!invalid(Some(0.), None).is_valid());
+        assert!(!invalid(None, Some(-1.)).is_valid());
+    }
 }
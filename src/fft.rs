@@ -0,0 +1,131 @@
+use std::f32::consts::PI;
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    pub fn from_polar(magnitude: f32, phase: f32) -> Self {
+        Self::new(magnitude * phase.cos(), magnitude * phase.sin())
+    }
+
+    pub fn norm(&self) -> f32 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(&self) -> f32 {
+        self.im.atan2(self.re)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+// In-place iterative radix-2 Cooley-Tukey transform. The length of `data`
+// must be a power of two.
+pub fn fft(data: &mut [Complex]) {
+    let n = data.len();
+    assert!(n.is_power_of_two(), "fft length must be a power of two");
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let step = Complex::from_polar(1., -2. * PI / len as f32);
+        for start in (0..n).step_by(len) {
+            let mut w = Complex::new(1., 0.);
+            for k in 0..len / 2 {
+                let even = data[start + k];
+                let odd = data[start + k + len / 2] * w;
+                data[start + k] = even + odd;
+                data[start + k + len / 2] = even - odd;
+                w = w * step;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// Transforms a real signal and returns the non-negative frequency bins
+// (DC up to and including Nyquist).
+pub fn real_fft(signal: &[f32]) -> Vec<Complex> {
+    let mut data: Vec<Complex> = signal.iter().map(|&x| Complex::new(x, 0.)).collect();
+    fft(&mut data);
+    data.truncate(signal.len() / 2 + 1);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fft_impulse() {
+        let mut signal = [0.; 8];
+        signal[0] = 1.;
+        let spectrum = real_fft(&signal);
+        assert_eq!(spectrum.len(), 5);
+        for bin in spectrum {
+            assert!((bin.norm() - 1.).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_fft_sine_peak() {
+        let n = 64;
+        let signal: Vec<f32> = (0..n)
+            .map(|i| (2. * PI * 4. * i as f32 / n as f32).sin())
+            .collect();
+        let spectrum = real_fft(&signal);
+        let peak = spectrum
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.norm().partial_cmp(&b.1.norm()).unwrap())
+            .map(|(i, _)| i)
+            .unwrap();
+        assert_eq!(peak, 4);
+        assert!((spectrum[4].norm() - n as f32 / 2.).abs() < 1e-3);
+    }
+}
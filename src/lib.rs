@@ -1,9 +1,35 @@
+mod bands;
+mod beat;
 mod bpm;
+mod fft;
+mod multichannel;
+mod offline;
+mod resample;
+mod ring;
+mod tempo;
+#[cfg(test)]
+mod test_utils;
+mod tracking;
 mod utils;
+mod window;
 
-use crate::bpm::FrameProcessor;
 use wasm_bindgen::prelude::*;
 
+pub use crate::bands::{Band, MAX_BANDS};
+pub use crate::bpm::{
+    FrameProcessor, FrameProcessorConfig, OdfOptions, OnsetDetectionMode, OnsetEvent,
+    ProcessResult, ProcessorState, ThresholdParams,
+};
+pub use crate::multichannel::{
+    deinterleave, downmix, ChannelStrategy, Downmix, Downmixer, MultiChannelProcessor,
+};
+pub use crate::offline::{
+    analyze, analyze_with_config, estimate_tempo, tempogram, Analysis, Tempogram,
+};
+pub use crate::tempo::{Tempo, TempoParams, TempoPrior};
+pub use crate::tracking::{TempoChange, TempoPoint, TrackingParams};
+pub use crate::window::Window;
+
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
 // allocator.
 #[cfg(feature = "wee_alloc")]
@@ -11,13 +37,482 @@ use wasm_bindgen::prelude::*;
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 #[wasm_bindgen]
-extern "C" {
-    fn alert(s: &str);
+pub struct OnsetDetector {
+    channels: MultiChannelProcessor,
+    // Strategy used while `per_channel` is off.
+    downmix: Downmix,
+    per_channel: bool,
+    last_onset: Option<OnsetEvent>,
+    // Tempo change confirmed during the last call to `process`.
+    tempo_change: Option<TempoChange>,
 }
 
 #[wasm_bindgen]
-pub fn greet() {
-    let mut processor = FrameProcessor::new();
-    processor.process([0.; 512]);
-    alert("Hello, bpm!");
+impl OnsetDetector {
+    // Omitted arguments fall back to `FrameProcessorConfig::default()`.
+    // Throws unless the sample rate, frame size and hop size are positive.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        mode: Option<OnsetDetectionMode>,
+        sample_rate: Option<f32>,
+        frame_size: Option<usize>,
+        hop_size: Option<usize>,
+    ) -> Result<OnsetDetector, JsError> {
+        utils::set_panic_hook();
+        let default = FrameProcessorConfig::default();
+        let config = FrameProcessorConfig {
+            mode: mode.unwrap_or(default.mode),
+            sample_rate: sample_rate.unwrap_or(default.sample_rate),
+            frame_size: frame_size.unwrap_or(default.frame_size),
+            hop_size: hop_size.unwrap_or(default.hop_size),
+            ..default
+        };
+        if config.sample_rate.is_nan() || config.sample_rate <= 0. {
+            return Err(JsError::new("sample rate must be positive"));
+        }
+        if config.frame_size == 0 || config.hop_size == 0 {
+            return Err(JsError::new("frame and hop sizes must be positive"));
+        }
+        Ok(Self {
+            channels: MultiChannelProcessor::new(
+                1,
+                ChannelStrategy::Downmix(Downmix::Average),
+                config,
+            ),
+            downmix: Downmix::Average,
+            per_channel: false,
+            last_onset: None,
+            tempo_change: None,
+        })
+    }
+
+    // Accepts any number of samples, e.g. a 128-sample AudioWorklet render
+    // quantum, and returns whether an onset was detected in any of the
+    // buffers completed by them.
+    pub fn process(&mut self, samples: &[f32]) -> bool {
+        let result = self.channels.process_planar(&[samples]);
+        self.update(result)
+    }
+
+    // Accepts `channels` equally long blocks of samples back to back, as
+    // copied from an AudioWorklet input, and downmixes them with the
+    // `downmix` strategy before processing unless `perChannel` is set.
+    #[wasm_bindgen(js_name = processPlanar)]
+    pub fn process_planar(&mut self, samples: &[f32], channels: usize) -> bool {
+        let length = samples.len() / channels.max(1);
+        let planar: Vec<&[f32]> = samples.chunks_exact(length.max(1)).collect();
+        let result = self.channels.process_planar(&planar);
+        self.update(result)
+    }
+
+    // Accepts interleaved samples of `channels` channels, handled like
+    // `processPlanar`.
+    #[wasm_bindgen(js_name = processInterleaved)]
+    pub fn process_interleaved(&mut self, samples: &[f32], channels: usize) -> bool {
+        let planar = deinterleave(samples, channels);
+        let planar: Vec<&[f32]> = planar.iter().map(|channel| &channel[..]).collect();
+        let result = self.channels.process_planar(&planar);
+        self.update(result)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn downmix(&self) -> Downmix {
+        self.downmix
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_downmix(&mut self, downmix: Downmix) {
+        self.downmix = downmix;
+        if !self.per_channel {
+            self.channels
+                .set_strategy(ChannelStrategy::Downmix(downmix));
+        }
+    }
+
+    // Runs a processor per channel instead of downmixing and merges their
+    // onsets. Tempo, beat and threshold readings then come from the channel
+    // with the most confident tempo. Changing this resets the detector.
+    #[wasm_bindgen(getter, js_name = perChannel)]
+    pub fn per_channel(&self) -> bool {
+        self.per_channel
+    }
+
+    #[wasm_bindgen(setter, js_name = perChannel)]
+    pub fn set_per_channel(&mut self, per_channel: bool) {
+        if per_channel == self.per_channel {
+            return;
+        }
+        self.per_channel = per_channel;
+        self.reset(self.channels.config().clone());
+    }
+
+    // Configures frequency bands from `[low_hz, high_hz, weight, ...]`
+    // triplets; an empty array returns to broadband detection. Bands past
+    // the 32nd are ignored. This resets the detector.
+    #[wasm_bindgen(js_name = setBands)]
+    pub fn set_bands(&mut self, bands: &[f32]) {
+        let bands = bands
+            .chunks_exact(3)
+            .take(MAX_BANDS)
+            .map(|band| Band::new(band[0], band[1], band[2]))
+            .collect();
+        self.reset(FrameProcessorConfig {
+            bands,
+            ..self.processor().config().clone()
+        });
+    }
+
+    // Rate the input is resampled to before analysis, `undefined` when it is
+    // analysed at the input rate.
+    #[wasm_bindgen(getter, js_name = analysisRate)]
+    pub fn analysis_rate(&self) -> Option<f32> {
+        self.processor().config().analysis_rate
+    }
+
+    // Non-positive rates are ignored. This resets the detector.
+    #[wasm_bindgen(setter, js_name = analysisRate)]
+    pub fn set_analysis_rate(&mut self, analysis_rate: Option<f32>) {
+        if analysis_rate.is_some_and(|rate| rate.is_nan() || rate <= 0.) {
+            return;
+        }
+        self.reset(FrameProcessorConfig {
+            analysis_rate,
+            ..self.processor().config().clone()
+        });
+    }
+
+    // Bit mask of the bands that fired with the most recent onset, bit `i`
+    // standing for the `i`th band passed to `setBands`.
+    #[wasm_bindgen(getter, js_name = onsetBands)]
+    pub fn onset_bands(&self) -> Option<u32> {
+        self.last_onset.map(|onset| onset.bands)
+    }
+
+    // Seconds since the first processed sample at which the most recent
+    // onset occurred.
+    #[wasm_bindgen(getter, js_name = onsetTime)]
+    pub fn onset_time(&self) -> Option<f32> {
+        self.last_onset.map(|onset| onset.time)
+    }
+
+    // ODF value of the most recent onset.
+    #[wasm_bindgen(getter, js_name = onsetStrength)]
+    pub fn onset_strength(&self) -> Option<f32> {
+        self.last_onset.map(|onset| onset.strength)
+    }
+
+    // Threshold the most recent onset exceeded.
+    #[wasm_bindgen(getter, js_name = onsetThreshold)]
+    pub fn onset_threshold(&self) -> Option<f32> {
+        self.last_onset.map(|onset| onset.threshold)
+    }
+
+    // False while the detector is still warming up and not reporting onsets.
+    #[wasm_bindgen(getter)]
+    pub fn ready(&self) -> bool {
+        self.processor().state() == ProcessorState::Ready
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn threshold(&self) -> f32 {
+        self.processor().threshold()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn window(&self) -> Window {
+        self.processor().config().window()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_window(&mut self, window: Window) {
+        self.channels
+            .configure(|processor| processor.set_window(window));
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn rectify(&self) -> bool {
+        self.processor().config().odf.rectify
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_rectify(&mut self, rectify: bool) {
+        let options = self.processor().config().odf;
+        self.set_odf_options(OdfOptions { rectify, ..options });
+    }
+
+    // γ of the log(1 + γ × ODF) compression, or undefined when disabled.
+    #[wasm_bindgen(getter, js_name = logCompression)]
+    pub fn log_compression(&self) -> Option<f32> {
+        self.processor().config().odf.log_compression
+    }
+
+    // Ignored unless positive.
+    #[wasm_bindgen(setter, js_name = logCompression)]
+    pub fn set_log_compression(&mut self, log_compression: Option<f32>) {
+        let options = self.processor().config().odf;
+        self.set_odf_options(OdfOptions {
+            log_compression,
+            ..options
+        });
+    }
+
+    // Time constant in seconds of the adaptive normalization, or undefined
+    // when disabled.
+    #[wasm_bindgen(getter)]
+    pub fn normalization(&self) -> Option<f32> {
+        self.processor().config().odf.normalization
+    }
+
+    // Ignored unless positive.
+    #[wasm_bindgen(setter)]
+    pub fn set_normalization(&mut self, normalization: Option<f32>) {
+        let options = self.processor().config().odf;
+        self.set_odf_options(OdfOptions {
+            normalization,
+            ..options
+        });
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn lambda(&self) -> f32 {
+        self.processor().threshold_params().lambda
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_lambda(&mut self, lambda: f32) {
+        let params = self.processor().threshold_params();
+        self.channels.configure(|processor| {
+            processor.set_threshold_params(ThresholdParams { lambda, ..params })
+        });
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn alpha(&self) -> f32 {
+        self.processor().threshold_params().alpha
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_alpha(&mut self, alpha: f32) {
+        let params = self.processor().threshold_params();
+        self.channels.configure(|processor| {
+            processor.set_threshold_params(ThresholdParams { alpha, ..params })
+        });
+    }
+
+    // Number of recent ODF values the threshold's median and mean cover.
+    #[wasm_bindgen(getter, js_name = thresholdWindow)]
+    pub fn threshold_window(&self) -> usize {
+        self.processor().threshold_params().m
+    }
+
+    // Clamped to at least one value.
+    #[wasm_bindgen(setter, js_name = thresholdWindow)]
+    pub fn set_threshold_window(&mut self, m: usize) {
+        let params = self.processor().threshold_params();
+        self.channels.configure(|processor| {
+            processor.set_threshold_params(ThresholdParams {
+                m: m.max(1),
+                ..params
+            })
+        });
+    }
+
+    #[wasm_bindgen(getter, js_name = peakWeight)]
+    pub fn peak_weight(&self) -> f32 {
+        self.processor().threshold_params().hp_weight
+    }
+
+    #[wasm_bindgen(setter, js_name = peakWeight)]
+    pub fn set_peak_weight(&mut self, hp_weight: f32) {
+        let params = self.processor().threshold_params();
+        self.channels.configure(|processor| {
+            processor.set_threshold_params(ThresholdParams {
+                hp_weight,
+                ..params
+            })
+        });
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn odf(&self) -> f32 {
+        self.processor().odf()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tempo(&self) -> Option<f32> {
+        self.processor().tempo().map(|tempo| tempo.bpm)
+    }
+
+    // Tempo smoothed over successive estimates, which follows gradual drift
+    // without the jitter of `tempo`.
+    #[wasm_bindgen(getter, js_name = smoothedTempo)]
+    pub fn smoothed_tempo(&self) -> Option<f32> {
+        self.processor().smoothed_tempo().map(|tempo| tempo.bpm)
+    }
+
+    // New tempo when an abrupt tempo change was confirmed during the last
+    // call to `process`.
+    #[wasm_bindgen(getter, js_name = tempoChange)]
+    pub fn tempo_change(&self) -> Option<f32> {
+        self.tempo_change.map(|change| change.to_bpm)
+    }
+
+    // Tempos in BPM of the entries of `tempogramColumn`.
+    #[wasm_bindgen(getter, js_name = tempogramBpms)]
+    pub fn tempogram_bpms(&self) -> Vec<f32> {
+        self.processor().tempogram_bpms()
+    }
+
+    // Tempogram column over the recent history, with a value in [0, 1] per
+    // tempo, or undefined until enough audio has been processed.
+    #[wasm_bindgen(js_name = tempogramColumn)]
+    pub fn tempogram_column(&self) -> Option<Vec<f32>> {
+        self.processor().tempogram_column()
+    }
+
+    #[wasm_bindgen(getter, js_name = minBpm)]
+    pub fn min_bpm(&self) -> f32 {
+        self.processor().tempo_params().min_bpm
+    }
+
+    // Ignored when it would leave `minBpm` at or above `maxBpm`; use
+    // `setTempoRange` to move both bounds at once.
+    #[wasm_bindgen(setter, js_name = minBpm)]
+    pub fn set_min_bpm(&mut self, min_bpm: f32) {
+        self.set_tempo_range(min_bpm, self.max_bpm());
+    }
+
+    #[wasm_bindgen(getter, js_name = maxBpm)]
+    pub fn max_bpm(&self) -> f32 {
+        self.processor().tempo_params().max_bpm
+    }
+
+    #[wasm_bindgen(setter, js_name = maxBpm)]
+    pub fn set_max_bpm(&mut self, max_bpm: f32) {
+        self.set_tempo_range(self.min_bpm(), max_bpm);
+    }
+
+    // Sets the tempo range the estimate is limited to. Returns false and
+    // leaves the range unchanged unless `0 < minBpm < maxBpm`.
+    #[wasm_bindgen(js_name = setTempoRange)]
+    pub fn set_tempo_range(&mut self, min_bpm: f32, max_bpm: f32) -> bool {
+        let params = TempoParams {
+            min_bpm,
+            max_bpm,
+            ..self.processor().tempo_params()
+        };
+        self.set_tempo_params(params)
+    }
+
+    // Centre of the tempo prior used to resolve half and double tempo
+    // ambiguity, or undefined to pick the strongest periodicity.
+    #[wasm_bindgen(getter, js_name = preferredBpm)]
+    pub fn preferred_bpm(&self) -> Option<f32> {
+        self.processor().tempo_params().prior.map(|prior| prior.bpm)
+    }
+
+    // Ignored unless positive.
+    #[wasm_bindgen(setter, js_name = preferredBpm)]
+    pub fn set_preferred_bpm(&mut self, bpm: Option<f32>) {
+        let params = self.processor().tempo_params();
+        let width = params.prior.unwrap_or_default().width;
+        self.set_tempo_params(TempoParams {
+            prior: bpm.map(|bpm| TempoPrior { bpm, width }),
+            ..params
+        });
+    }
+
+    #[wasm_bindgen(getter, js_name = tempoConfidence)]
+    pub fn tempo_confidence(&self) -> Option<f32> {
+        self.processor().tempo().map(|tempo| tempo.confidence)
+    }
+
+    // Whether a beat was predicted during the last call to `process`.
+    #[wasm_bindgen(getter)]
+    pub fn beat(&self) -> bool {
+        self.processor().beat()
+    }
+
+    // Whether one of the beats predicted during the last call to `process`
+    // was the first of a bar.
+    #[wasm_bindgen(getter)]
+    pub fn downbeat(&self) -> bool {
+        self.processor().downbeat()
+    }
+
+    // Position of the most recent beat in its bar, from 1 to `beatsPerBar`.
+    #[wasm_bindgen(getter, js_name = barPosition)]
+    pub fn bar_position(&self) -> Option<usize> {
+        self.processor().bar_position()
+    }
+
+    #[wasm_bindgen(getter, js_name = beatsPerBar)]
+    pub fn beats_per_bar(&self) -> usize {
+        self.processor().config().beats_per_bar
+    }
+
+    // Clamped to at least one beat.
+    #[wasm_bindgen(setter, js_name = beatsPerBar)]
+    pub fn set_beats_per_bar(&mut self, beats_per_bar: usize) {
+        self.channels
+            .configure(|processor| processor.set_beats_per_bar(beats_per_bar.max(1)));
+    }
+
+    // Seconds since the first processed sample at which the next beat is
+    // expected.
+    #[wasm_bindgen(getter, js_name = nextBeatTime)]
+    pub fn next_beat_time(&self) -> Option<f32> {
+        self.processor().next_beat_time()
+    }
+}
+
+impl OnsetDetector {
+    // Starts over with a processor built from `config`.
+    fn reset(&mut self, config: FrameProcessorConfig) {
+        let strategy = match self.per_channel {
+            true => ChannelStrategy::PerChannel,
+            false => ChannelStrategy::Downmix(self.downmix),
+        };
+        self.channels = MultiChannelProcessor::new(1, strategy, config);
+        self.last_onset = None;
+        self.tempo_change = None;
+    }
+
+    // Processor whose readings the getters report.
+    fn processor(&self) -> &FrameProcessor {
+        self.channels.processor()
+    }
+
+    // Records the outcome of processing a block and returns whether it
+    // contained an onset.
+    fn update(&mut self, result: ProcessResult) -> bool {
+        self.tempo_change = result.tempo_changes.last().copied();
+        match result.onsets.last() {
+            Some(&onset) => {
+                self.last_onset = Some(onset);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Applies `params` if they are valid, so setters called from JS cannot
+    // trip the estimator's assertions.
+    fn set_tempo_params(&mut self, params: TempoParams) -> bool {
+        match params.is_valid() {
+            true => {
+                self.channels
+                    .configure(|processor| processor.set_tempo_params(params));
+                true
+            }
+            false => false,
+        }
+    }
+
+    fn set_odf_options(&mut self, options: OdfOptions) {
+        if options.is_valid() {
+            self.channels
+                .configure(|processor| processor.set_odf_options(options));
+        }
+    }
 }
@@ -0,0 +1,456 @@
+use super::bpm::{FrameProcessor, FrameProcessorConfig, OnsetEvent, ProcessResult};
+use super::tempo::Tempo;
+use wasm_bindgen::prelude::*;
+
+// Onsets from different channels closer than this many hops are merged.
+const MERGE_TOLERANCE_FRAMES: usize = 2;
+
+// Time constant in samples of the channel energies `Downmixer` compares.
+const ENERGY_SMOOTHING: f32 = 4096.;
+
+// Factor by which another channel's smoothed energy has to exceed the
+// selected one's before `Downmixer` switches to it.
+const SWITCH_RATIO: f32 = 2.;
+
+// Length in samples of the crossfade when `Downmixer` switches channels.
+const CROSSFADE: usize = 1024;
+
+// Ways of reducing multi-channel audio to the mono signal the processor
+// analyses.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Downmix {
+    Average,
+    // The channel with the most energy. When streaming, see `Downmixer`, the
+    // channel only changes once another is clearly louder.
+    MaxEnergy,
+    // Sum of the first two channels, halved.
+    Mid,
+    // Difference of the first two channels, halved.
+    Side,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ChannelStrategy {
+    Downmix(Downmix),
+    // Runs a processor per channel and merges their onsets.
+    PerChannel,
+}
+
+// Splits interleaved samples into one buffer per channel. Trailing samples
+// that do not make up a whole sample frame are dropped.
+pub fn deinterleave(samples: &[f32], channels: usize) -> Vec<Vec<f32>> {
+    (0..channels)
+        .map(|channel| {
+            samples
+                .chunks_exact(channels)
+                .map(|frame| frame[channel])
+                .collect()
+        })
+        .collect()
+}
+
+pub fn downmix(channels: &[&[f32]], downmix: Downmix) -> Vec<f32> {
+    let length = channels
+        .iter()
+        .map(|channel| channel.len())
+        .min()
+        .unwrap_or(0);
+    let pair = |f: fn(f32, f32) -> f32| -> Vec<f32> {
+        match channels {
+            [] => vec![],
+            [mono] => mono.iter().map(|&x| f(x, 0.) * 2.).collect(),
+            [left, right, ..] => (0..length).map(|i| f(left[i], right[i])).collect(),
+        }
+    };
+    match downmix {
+        Downmix::Average => (0..length)
+            .map(|i| channels.iter().map(|channel| channel[i]).sum::<f32>() / channels.len() as f32)
+            .collect(),
+        Downmix::MaxEnergy => {
+            let energy = |channel: &[f32]| channel[..length].iter().map(|x| x * x).sum::<f32>();
+            channels
+                .iter()
+                .max_by(|a, b| energy(a).total_cmp(&energy(b)))
+                .map(|channel| channel[..length].to_vec())
+                .unwrap_or_default()
+        }
+        Downmix::Mid => pair(|left, right| (left + right) / 2.),
+        Downmix::Side => match channels.len() {
+            1 => vec![0.; length],
+            _ => pair(|left, right| (left - right) / 2.),
+        },
+    }
+}
+
+// Downmixes successive blocks of a stream. With `Downmix::MaxEnergy` the
+// channel is picked from smoothed energies with some hysteresis and faded
+// over when it changes, so that neither near-equal channels nor a switch
+// produce false onsets.
+pub struct Downmixer {
+    pub downmix: Downmix,
+    energies: Vec<f32>,
+    channel: usize,
+    // Channel being faded out and how many samples of the fade are done.
+    fade: Option<(usize, usize)>,
+}
+
+impl Downmixer {
+    pub fn new(downmix: Downmix) -> Self {
+        Self {
+            downmix,
+            energies: vec![],
+            channel: 0,
+            fade: None,
+        }
+    }
+
+    pub fn process(&mut self, channels: &[&[f32]]) -> Vec<f32> {
+        match self.downmix {
+            Downmix::MaxEnergy if !channels.is_empty() => self.max_energy(channels),
+            strategy => downmix(channels, strategy),
+        }
+    }
+
+    fn max_energy(&mut self, channels: &[&[f32]]) -> Vec<f32> {
+        let length = channels
+            .iter()
+            .map(|channel| channel.len())
+            .min()
+            .unwrap_or(0);
+        let energies = channels.iter().map(|channel| {
+            channel[..length].iter().map(|x| x * x).sum::<f32>() / length.max(1) as f32
+        });
+        match self.energies.len() == channels.len() {
+            true => {
+                let decay = (-(length as f32) / ENERGY_SMOOTHING).exp();
+                for (smoothed, energy) in self.energies.iter_mut().zip(energies) {
+                    *smoothed = *smoothed * decay + energy * (1. - decay);
+                }
+            }
+            // First block or a change of channel count: start on the
+            // loudest channel without fading.
+            false => {
+                self.energies = energies.collect();
+                self.channel = loudest(&self.energies);
+                self.fade = None;
+            }
+        }
+        let candidate = loudest(&self.energies);
+        if self.energies[candidate] > self.energies[self.channel] * SWITCH_RATIO {
+            self.fade = Some((self.channel, 0));
+            self.channel = candidate;
+        }
+        let current = &channels[self.channel][..length];
+        match self.fade {
+            Some((previous, done)) => {
+                self.fade = match done + length < CROSSFADE {
+                    true => Some((previous, done + length)),
+                    false => None,
+                };
+                current
+                    .iter()
+                    .zip(channels[previous].iter())
+                    .enumerate()
+                    .map(|(i, (x, y))| {
+                        let gain = ((done + i + 1) as f32 / CROSSFADE as f32).min(1.);
+                        x * gain + y * (1. - gain)
+                    })
+                    .collect()
+            }
+            None => current.to_vec(),
+        }
+    }
+}
+
+// Index of the largest of `energies`.
+fn loudest(energies: &[f32]) -> usize {
+    (0..energies.len())
+        .max_by(|&a, &b| energies[a].total_cmp(&energies[b]))
+        .unwrap_or(0)
+}
+
+// Runs onset detection over multi-channel audio, either on a downmix or on
+// each channel separately.
+pub struct MultiChannelProcessor {
+    strategy: ChannelStrategy,
+    processors: Vec<FrameProcessor>,
+    downmixer: Downmixer,
+}
+
+impl MultiChannelProcessor {
+    pub fn new(channels: usize, strategy: ChannelStrategy, config: FrameProcessorConfig) -> Self {
+        assert!(channels > 0, "at least one channel is required");
+        let (count, downmix) = match strategy {
+            ChannelStrategy::Downmix(downmix) => (1, downmix),
+            ChannelStrategy::PerChannel => (channels, Downmix::Average),
+        };
+        Self {
+            strategy,
+            processors: (0..count)
+                .map(|_| FrameProcessor::from_config(config.clone()))
+                .collect(),
+            downmixer: Downmixer::new(downmix),
+        }
+    }
+
+    pub fn process_interleaved(&mut self, samples: &[f32], channels: usize) -> ProcessResult {
+        let planar = deinterleave(samples, channels);
+        let planar: Vec<&[f32]> = planar.iter().map(|channel| &channel[..]).collect();
+        self.process_planar(&planar)
+    }
+
+    pub fn process_planar(&mut self, channels: &[&[f32]]) -> ProcessResult {
+        match self.strategy {
+            ChannelStrategy::Downmix(_) => {
+                self.processors[0].process(&self.downmixer.process(channels))
+            }
+            ChannelStrategy::PerChannel if channels.is_empty() => self.processors[0].process(&[]),
+            ChannelStrategy::PerChannel => {
+                // Channels that appear start out with fresh processors.
+                let config = self.config().clone();
+                self.processors.resize_with(channels.len(), || {
+                    FrameProcessor::from_config(config.clone())
+                });
+                let results: Vec<ProcessResult> = self
+                    .processors
+                    .iter_mut()
+                    .zip(channels.iter())
+                    .map(|(processor, samples)| processor.process(samples))
+                    .collect();
+                // Tempo changes come from the channel with the most
+                // confident tempo, as reported by `tempo`.
+                let tempo_changes = results[self.most_confident()].tempo_changes.clone();
+                ProcessResult {
+                    state: results[0].state,
+                    onsets: merge_onsets(results.into_iter().flat_map(|result| result.onsets)),
+                    tempo_changes,
+                }
+            }
+        }
+    }
+
+    pub fn strategy(&self) -> ChannelStrategy {
+        self.strategy
+    }
+
+    // Switching between downmixing and per-channel processing starts over
+    // with fresh processors built from the current configuration.
+    pub fn set_strategy(&mut self, strategy: ChannelStrategy) {
+        match (self.strategy, strategy) {
+            (ChannelStrategy::Downmix(_), ChannelStrategy::Downmix(downmix)) => {
+                self.downmixer.downmix = downmix;
+                self.strategy = strategy;
+            }
+            (ChannelStrategy::PerChannel, ChannelStrategy::PerChannel) => {}
+            _ => {
+                let config = self.config().clone();
+                *self = Self::new(1, strategy, config);
+            }
+        }
+    }
+
+    // Configuration shared by the processors.
+    pub fn config(&self) -> &FrameProcessorConfig {
+        self.processors[0].config()
+    }
+
+    // Applies a setting to the processor of every channel.
+    pub fn configure(&mut self, mut f: impl FnMut(&mut FrameProcessor)) {
+        for processor in &mut self.processors {
+            f(processor);
+        }
+    }
+
+    // The processor with the most confident tempo estimate, or the only one
+    // when downmixing.
+    pub fn processor(&self) -> &FrameProcessor {
+        &self.processors[self.most_confident()]
+    }
+
+    // The most confident tempo estimate among the channels.
+    pub fn tempo(&self) -> Option<Tempo> {
+        self.processors[self.most_confident()].tempo()
+    }
+
+    pub fn smoothed_tempo(&self) -> Option<Tempo> {
+        self.processors[self.most_confident()].smoothed_tempo()
+    }
+
+    // Whether any channel predicted a beat during the last call.
+    pub fn beat(&self) -> bool {
+        self.processors.iter().any(|processor| processor.beat())
+    }
+
+    // Index of the processor with the most confident tempo estimate.
+    fn most_confident(&self) -> usize {
+        let confidence = |i: &usize| self.processors[*i].tempo().map_or(0., |t| t.confidence);
+        (0..self.processors.len())
+            .max_by(|a, b| confidence(a).total_cmp(&confidence(b)))
+            .unwrap_or(0)
+    }
+}
+
+// Sorts onsets by position and merges those within the tolerance of each
+// other, keeping the strongest and combining their fired bands.
+fn merge_onsets(onsets: impl Iterator<Item = OnsetEvent>) -> Vec<OnsetEvent> {
+    let mut onsets: Vec<OnsetEvent> = onsets.collect();
+    onsets.sort_by_key(|onset| onset.frame);
+    let mut merged: Vec<OnsetEvent> = vec![];
+    for onset in onsets {
+        match merged.last_mut() {
+            Some(last) if onset.frame - last.frame <= MERGE_TOLERANCE_FRAMES => {
+                let bands = last.bands | onset.bands;
+                if onset.strength > last.strength {
+                    *last = onset;
+                }
+                last.bands = bands;
+            }
+            _ => merged.push(onset),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::clicks;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_downmix() {
+        let left: &[f32] = &[1., 0., 0.5];
+        let right: &[f32] = &[0., 0., -0.5];
+        assert_eq!(downmix(&[left, right], Downmix::Average), vec![0.5, 0., 0.]);
+        assert_eq!(downmix(&[left, right], Downmix::Mid), vec![0.5, 0., 0.]);
+        assert_eq!(downmix(&[left, right], Downmix::Side), vec![0.5, 0., 0.5]);
+        assert_eq!(downmix(&[right, left], Downmix::MaxEnergy), left.to_vec());
+        assert_eq!(downmix(&[left], Downmix::Side), vec![0.; 3]);
+        assert_eq!(
+            deinterleave(&[1., 2., 3., 4., 5.], 2),
+            vec![vec![1., 3.], vec![2., 4.]]
+        );
+    }
+
+    #[test]
+    fn test_downmixer_max_energy() {
+        let sample_rate = 44100.;
+        let tone = |frequency: f32, amplitude: f32| -> Vec<f32> {
+            (0..sample_rate as usize * 4)
+                .map(|i| amplitude * (2. * PI * frequency * i as f32 / sample_rate).sin())
+                .collect()
+        };
+        // Steady tones at nearly the same level, which a per-block choice
+        // keeps switching between.
+        let (left, right) = (tone(440., 0.5), tone(660., 0.49));
+        let config = FrameProcessorConfig::default();
+        let mut blockwise = FrameProcessor::from_config(config.clone());
+        let mut smoothed = FrameProcessor::from_config(config.clone());
+        let mut downmixer = Downmixer::new(Downmix::MaxEnergy);
+        let (mut blockwise_onsets, mut smoothed_onsets) = (0, 0);
+        for (left, right) in left.chunks(128).zip(right.chunks(128)) {
+            let block = downmix(&[left, right], Downmix::MaxEnergy);
+            blockwise_onsets += blockwise.process(&block).onsets.len();
+            let block = downmixer.process(&[left, right]);
+            smoothed_onsets += smoothed.process(&block).onsets.len();
+        }
+        // Compared with the left channel alone, switching adds onsets while
+        // the smoothed choice adds none.
+        let mut mono = FrameProcessor::from_config(config);
+        let mono_onsets: usize = left
+            .chunks(128)
+            .map(|block| mono.process(block).onsets.len())
+            .sum();
+        assert!(blockwise_onsets > mono_onsets);
+        assert_eq!(smoothed_onsets, mono_onsets);
+
+        // A clearly louder channel is followed, without a jump.
+        let (quiet, loud) = (vec![0.1; 1024], vec![1.; 1024]);
+        let mut downmixer = Downmixer::new(Downmix::MaxEnergy);
+        assert_eq!(downmixer.process(&[&loud, &quiet]), loud);
+        let samples: Vec<f32> = (0..8)
+            .flat_map(|_| downmixer.process(&[&quiet, &loud]))
+            .collect();
+        assert_eq!(samples[0], 0.1);
+        assert_eq!(samples.last(), Some(&1.));
+        assert!(samples
+            .windows(2)
+            .all(|pair| (0. ..=1. / CROSSFADE as f32).contains(&(pair[1] - pair[0]))));
+    }
+
+    #[test]
+    fn test_per_channel_processing() {
+        let sample_rate = 44100.;
+        // 100 BPM for 6 seconds, then 140 BPM.
+        let left = [clicks(100., 6., sample_rate), clicks(140., 6., sample_rate)].concat();
+        // The right channel plays the 100 BPM part a quarter second late and
+        // then falls silent, so only the left channel sees the change.
+        let delay = (sample_rate / 4.) as usize;
+        let mut right = vec![0.; delay];
+        right.extend(clicks(100., 3., sample_rate));
+        right.resize(left.len(), 0.);
+
+        let config = FrameProcessorConfig::default();
+        let mut processor =
+            MultiChannelProcessor::new(2, ChannelStrategy::PerChannel, config.clone());
+        let mut mono = FrameProcessor::from_config(config);
+        let (mut onsets, mut mono_onsets, mut changes) = (vec![], 0, vec![]);
+        for (left, right) in left.chunks(4096).zip(right.chunks(4096)) {
+            let result = processor.process_planar(&[left, right]);
+            onsets.extend(result.onsets);
+            changes.extend(result.tempo_changes);
+            mono_onsets += mono.process(left).onsets.len();
+        }
+
+        // The delayed onsets are kept apart from the left channel's.
+        assert!(
+            onsets.len() > mono_onsets,
+            "{} {}",
+            onsets.len(),
+            mono_onsets
+        );
+        assert!(onsets
+            .windows(2)
+            .all(|pair| pair[1].frame - pair[0].frame > MERGE_TOLERANCE_FRAMES));
+        // Tempo changes and the tempo follow the more confident left channel.
+        assert_eq!(changes.len(), 1, "{:?}", changes);
+        assert!((changes[0].to_bpm - 140.).abs() < 2., "{:?}", changes);
+        assert!((processor.tempo().unwrap().bpm - 140.).abs() < 2.);
+    }
+
+    #[test]
+    fn test_strategy_changes() {
+        let config = FrameProcessorConfig::default();
+        let mut processor = MultiChannelProcessor::new(2, ChannelStrategy::PerChannel, config);
+        let silence: &[f32] = &[0.; 128];
+        processor.process_planar(&[silence, silence]);
+        // The channel count may change between calls.
+        processor.process_planar(&[silence; 3]);
+        assert_eq!(processor.processors.len(), 3);
+        processor.process_planar(&[silence]);
+        assert_eq!(processor.processors.len(), 1);
+
+        processor.configure(|processor| processor.set_beats_per_bar(3));
+        processor.set_strategy(ChannelStrategy::Downmix(Downmix::Mid));
+        assert_eq!(processor.strategy(), ChannelStrategy::Downmix(Downmix::Mid));
+        assert_eq!(processor.config().beats_per_bar, 3);
+        processor.set_strategy(ChannelStrategy::Downmix(Downmix::Side));
+        assert_eq!(processor.downmixer.downmix, Downmix::Side);
+    }
+
+    #[test]
+    fn test_merge_onsets() {
+        let onset = |frame: usize, strength: f32, bands: u32| OnsetEvent {
+            frame,
+            sample: frame * 512,
+            time: 0.,
+            strength,
+            threshold: 0.,
+            bands,
+        };
+        let merged =
+            merge_onsets(vec![onset(10, 1., 1), onset(30, 1., 0), onset(11, 2., 2)].into_iter());
+        assert_eq!(merged, vec![onset(11, 2., 3), onset(30, 1., 0)]);
+    }
+}
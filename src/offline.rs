@@ -0,0 +1,248 @@
+use super::bpm::{tempo_horizon, tracking_step};
+use super::bpm::{FrameProcessor, FrameProcessorConfig, OnsetEvent, ThresholdParams};
+use super::tempo::{Tempo, TempoEstimator};
+use super::tracking::{TempoChange, TempoPoint, TempoTracker};
+use super::utils::{mean, median};
+
+// A peak must be the largest ODF value within this many hops on either side.
+const PEAK_RADIUS: usize = 3;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Analysis {
+    pub onsets: Vec<OnsetEvent>,
+    // One post-processed ODF value per hop.
+    pub odf: Vec<f32>,
+    // Tempo estimated over the whole signal.
+    pub tempo: Option<Tempo>,
+    // Smoothed tempo over time, one point per tracking step.
+    pub tempo_curve: Vec<TempoPoint>,
+    pub tempo_changes: Vec<TempoChange>,
+    pub tempogram: Tempogram,
+}
+
+// Tempo-versus-time matrix of the ODF's normalized autocorrelation.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Tempogram {
+    // Tempo of each row, in steps of 1 BPM across the configured range.
+    pub bpms: Vec<f32>,
+    // ODF frame each column is centred on, one per tracking step.
+    pub frames: Vec<usize>,
+    // One column per frame holding a value in [0, 1] per row.
+    pub columns: Vec<Vec<f32>>,
+}
+
+// Analyses a complete mono signal with the default configuration.
+pub fn analyze(signal: &[f32], sample_rate: f32) -> Analysis {
+    analyze_with_config(
+        signal,
+        FrameProcessorConfig {
+            sample_rate,
+            ..FrameProcessorConfig::default()
+        },
+    )
+}
+
+// Analyses a complete mono signal. Unlike `FrameProcessor::process`, peak
+// picking is non-causal: thresholds are centred on each frame and peaks are
+// compared with the frames after them. Band onsets are not reported.
+pub fn analyze_with_config(signal: &[f32], config: FrameProcessorConfig) -> Analysis {
+    let odf = FrameProcessor::from_config(config.clone()).odf_curve(signal);
+    let onsets = pick_peaks(&odf, &config.threshold)
+        .into_iter()
+        .map(|(frame, threshold)| OnsetEvent {
+            frame,
+            sample: config.onset_sample(frame),
+            time: config.frame_to_seconds(config.onset_position(frame)),
+            strength: odf[frame],
+            threshold,
+            bands: 0,
+        })
+        .collect();
+    let tempo = estimate_tempo(&odf, &config);
+    let (tempo_curve, tempo_changes) = track_tempo(&odf, &config);
+    let tempogram = tempogram(&odf, &config);
+    Analysis {
+        onsets,
+        odf,
+        tempo,
+        tempo_curve,
+        tempo_changes,
+        tempogram,
+    }
+}
+
+// Estimates a single tempo over a whole ODF, such as `Analysis::odf`.
+pub fn estimate_tempo(odf: &[f32], config: &FrameProcessorConfig) -> Option<Tempo> {
+    TempoEstimator::new(config.frame_rate(), config.tempo).estimate(odf)
+}
+
+// Computes the tempogram of an ODF, such as `Analysis::odf`, over windows of
+// the streaming tempo horizon centred on every tracking step. It is empty
+// when the ODF is too short to cover two periods of the slowest tempo.
+pub fn tempogram(odf: &[f32], config: &FrameProcessorConfig) -> Tempogram {
+    let estimator = TempoEstimator::new(config.frame_rate(), config.tempo);
+    let window = tempo_horizon(config).min(odf.len());
+    let (frames, columns) = (0..odf.len())
+        .step_by(tracking_step(config))
+        .filter_map(|frame| {
+            let start = window_start(frame, window, odf.len());
+            let column = estimator.tempogram_column(&odf[start..start + window])?;
+            Some((frame, column))
+        })
+        .unzip();
+    Tempogram {
+        bpms: estimator.tempogram_bpms(),
+        frames,
+        columns,
+    }
+}
+
+// Estimates the tempo over windows of the streaming tempo horizon centred on
+// every tracking step and smooths the estimates as `FrameProcessor` does.
+fn track_tempo(odf: &[f32], config: &FrameProcessorConfig) -> (Vec<TempoPoint>, Vec<TempoChange>) {
+    let estimator = TempoEstimator::new(config.frame_rate(), config.tempo);
+    let mut tracker = TempoTracker::new(config.tracking);
+    let window = tempo_horizon(config).min(odf.len());
+    let mut curve = vec![];
+    let mut changes = vec![];
+    for frame in (0..odf.len()).step_by(tracking_step(config)) {
+        let start = window_start(frame, window, odf.len());
+        let estimate = match estimator.estimate(&odf[start..start + window]) {
+            Some(estimate) => estimate,
+            None => continue,
+        };
+        let time = config.frame_to_seconds(frame as f32);
+        if let Some((from_bpm, to_bpm)) = tracker.update(estimate) {
+            changes.push(TempoChange {
+                frame,
+                time,
+                from_bpm,
+                to_bpm,
+            });
+        }
+        if let Some(tempo) = tracker.tempo() {
+            curve.push(TempoPoint { frame, time, tempo });
+        }
+    }
+    (curve, changes)
+}
+
+// Start of the window of `window` frames centred on `frame`, moved inwards
+// where it would extend past either end of the ODF.
+fn window_start(frame: usize, window: usize, len: usize) -> usize {
+    frame.saturating_sub(window / 2).min(len - window)
+}
+
+// Returns the frames that are local maxima above a threshold computed over a
+// window of `params.m` values centred on them, with that threshold. The
+// highest-peak term uses the largest value in the whole ODF.
+fn pick_peaks(odf: &[f32], params: &ThresholdParams) -> Vec<(usize, f32)> {
+    let global_peak = odf.iter().cloned().fold(0., f32::max);
+    let half_window = params.m / 2;
+    (0..odf.len())
+        .filter_map(|n| {
+            let neighbourhood =
+                &odf[n.saturating_sub(PEAK_RADIUS)..(n + PEAK_RADIUS + 1).min(odf.len())];
+            if neighbourhood.iter().any(|&x| x > odf[n]) || odf[n] <= 0. {
+                return None;
+            }
+            let window = &odf[n.saturating_sub(half_window)..(n + half_window + 1).min(odf.len())];
+            let threshold = params.lambda * median(window)
+                + params.alpha * mean(window)
+                + params.hp_weight * global_peak;
+            match odf[n] > threshold {
+                true => Some((n, threshold)),
+                false => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bpm::OdfOptions;
+    use crate::test_utils::{clicks, pulse_train};
+
+    #[test]
+    fn test_pick_peaks_looks_ahead() {
+        let odf = [0., 1., 0., 0., 5., 0., 0., 0., 4., 4.5, 0., 0.];
+        let peaks: Vec<usize> = pick_peaks(&odf, &ThresholdParams::default())
+            .into_iter()
+            .map(|(frame, _)| frame)
+            .collect();
+        // The 4 at frame 8 is not reported because a larger value follows.
+        assert_eq!(peaks, vec![4, 9]);
+    }
+
+    #[test]
+    fn test_analyze_clicks() {
+        let sample_rate = 44100.;
+        let signal = clicks(120., 8., sample_rate);
+        // Only rises in energy count, so each burst gives a single onset.
+        let analysis = analyze_with_config(
+            &signal,
+            FrameProcessorConfig {
+                sample_rate,
+                odf: OdfOptions {
+                    rectify: true,
+                    ..OdfOptions::default()
+                },
+                ..FrameProcessorConfig::default()
+            },
+        );
+        assert_eq!(analysis.odf.len(), signal.len().div_ceil(512));
+        assert_eq!(analysis.onsets.len(), 16);
+        let tempo = analysis.tempo.unwrap();
+        assert!((tempo.bpm - 120.).abs() < 2., "{}", tempo.bpm);
+    }
+
+    #[test]
+    fn test_analyze_tempo_change() {
+        let sample_rate = 44100.;
+        // 100 BPM for 8 seconds, then 140 BPM.
+        let signal = [clicks(100., 8., sample_rate), clicks(140., 8., sample_rate)].concat();
+        let analysis = analyze(&signal, sample_rate);
+
+        assert_eq!(
+            analysis.tempo_changes.len(),
+            1,
+            "{:?}",
+            analysis.tempo_changes
+        );
+        let change = analysis.tempo_changes[0];
+        assert!((change.from_bpm - 100.).abs() < 2., "{:?}", change);
+        assert!((change.to_bpm - 140.).abs() < 2., "{:?}", change);
+        assert!(change.time > 8. && change.time < 14., "{:?}", change);
+
+        let first = analysis.tempo_curve.first().unwrap();
+        let last = analysis.tempo_curve.last().unwrap();
+        assert!((first.tempo.bpm - 100.).abs() < 2.);
+        assert!((last.tempo.bpm - 140.).abs() < 2.);
+
+        // Streaming reports the same change a little later.
+        let mut processor = FrameProcessor::new();
+        let changes = processor.process(&signal).tempo_changes;
+        assert_eq!(changes.len(), 1, "{:?}", changes);
+        assert!((changes[0].to_bpm - 140.).abs() < 2., "{:?}", changes);
+        assert!(changes[0].time > change.time);
+    }
+
+    #[test]
+    fn test_tempogram() {
+        let config = FrameProcessorConfig::default();
+        // A pulse every 43 hops is close to 120 BPM at the default frame rate.
+        let odf = pulse_train(2000, 43);
+        let matrix = tempogram(&odf, &config);
+        assert_eq!(matrix.bpms.len(), 141);
+        assert_eq!(matrix.frames.len(), matrix.columns.len());
+        assert_eq!(matrix.frames[1], tracking_step(&config));
+        for column in &matrix.columns {
+            let strongest = (0..column.len())
+                .max_by(|&a, &b| column[a].total_cmp(&column[b]))
+                .unwrap();
+            assert!((matrix.bpms[strongest] - 120.).abs() <= 1.);
+        }
+        assert!(tempogram(&odf[..50], &config).columns.is_empty());
+    }
+}
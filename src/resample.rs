@@ -0,0 +1,155 @@
+use std::f32::consts::PI;
+
+// Zero crossings of the sinc kernel on either side of each output sample.
+const ZERO_CROSSINGS: f32 = 8.;
+// Number of sub-sample positions the kernel is precomputed for. Output
+// samples use the nearest one, which is within 1/2048 of an input sample.
+const PHASES: usize = 1024;
+
+// Streaming band-limited resampler using a Blackman-windowed sinc kernel.
+// When downsampling, the kernel's cutoff is lowered to the output Nyquist
+// frequency so higher frequencies do not alias.
+pub struct Resampler {
+    // Input samples per output sample.
+    step: f64,
+    // Kernel half width in input samples.
+    half_width: usize,
+    // Polyphase kernel table: for each of `PHASES + 1` fractional positions,
+    // the weights of the `2 × half_width` input samples around it, so no
+    // trigonometry is needed per output sample.
+    kernels: Vec<f32>,
+    // Buffered input. The first `half_width` values start out as silence so
+    // the first output sample lines up with the first input sample.
+    input: Vec<f32>,
+    // Position of the next output sample in `input`.
+    position: f64,
+    // Input samples received and output samples produced so far.
+    received: usize,
+    produced: usize,
+}
+
+impl Resampler {
+    pub fn new(input_rate: f32, output_rate: f32) -> Self {
+        assert!(
+            input_rate > 0. && output_rate > 0.,
+            "rates must be positive"
+        );
+        // Cutoff frequency relative to the input Nyquist frequency.
+        let cutoff = (output_rate / input_rate).min(1.);
+        let half_width = (ZERO_CROSSINGS / cutoff).ceil() as usize;
+        let width = half_width as f32;
+        let kernels = (0..=PHASES)
+            .flat_map(|phase| {
+                let fraction = phase as f32 / PHASES as f32;
+                (0..2 * half_width).map(move |tap| {
+                    let x = tap as f32 + 1. - width - fraction;
+                    match x.abs() < width {
+                        true => cutoff * sinc(cutoff * x) * blackman(x / width),
+                        false => 0.,
+                    }
+                })
+            })
+            .collect();
+        Self {
+            step: input_rate as f64 / output_rate as f64,
+            half_width,
+            kernels,
+            input: vec![0.; half_width],
+            position: half_width as f64,
+            received: 0,
+            produced: 0,
+        }
+    }
+
+    // Returns the output samples whose kernels are covered by the input seen
+    // so far, which lags the input by the kernel's half width.
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.received += samples.len();
+        self.input.extend_from_slice(samples);
+        let mut output = vec![];
+        while self.position.floor() as usize + self.half_width < self.input.len() {
+            output.push(self.interpolate());
+            self.position += self.step;
+        }
+        let consumed = (self.position.floor() as usize).saturating_sub(self.half_width);
+        self.input.drain(..consumed);
+        self.position -= consumed as f64;
+        self.produced += output.len();
+        output
+    }
+
+    // Pads the input with silence to return the output samples still held
+    // back, up to the length corresponding to all the input received.
+    pub fn flush(&mut self) -> Vec<f32> {
+        let expected = (self.received as f64 / self.step).ceil() as usize;
+        let remaining = expected.saturating_sub(self.produced);
+        let received = self.received;
+        let mut output = self.process(&vec![0.; self.half_width + 1]);
+        output.truncate(remaining);
+        self.received = received;
+        self.produced = expected;
+        output
+    }
+
+    fn interpolate(&self) -> f32 {
+        let center = self.position.floor() as usize;
+        let phase = ((self.position - center as f64) * PHASES as f64).round() as usize;
+        let taps = 2 * self.half_width;
+        let kernel = &self.kernels[phase * taps..(phase + 1) * taps];
+        self.input[center + 1 - self.half_width..=center + self.half_width]
+            .iter()
+            .zip(kernel.iter())
+            .map(|(x, weight)| x * weight)
+            .sum()
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    match x == 0. {
+        true => 1.,
+        false => (PI * x).sin() / (PI * x),
+    }
+}
+
+// Blackman window over [-1, 1].
+fn blackman(x: f32) -> f32 {
+    0.42 + 0.5 * (PI * x).cos() + 0.08 * (2. * PI * x).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resampler_length_and_passband() {
+        let input_rate = 48000.;
+        let output_rate = 44100.;
+        let tone = |rate: f32, i: usize| (2. * PI * 1000. * i as f32 / rate).sin();
+        let input: Vec<f32> = (0..48000).map(|i| tone(input_rate, i)).collect();
+
+        let mut resampler = Resampler::new(input_rate, output_rate);
+        let mut output = vec![];
+        for chunk in input.chunks(128) {
+            output.extend(resampler.process(chunk));
+        }
+        output.extend(resampler.flush());
+        assert_eq!(output.len(), 44100);
+        // Away from the edges the tone comes through unchanged.
+        for (i, &x) in output.iter().enumerate().skip(1000).take(100) {
+            assert!((x - tone(output_rate, i)).abs() < 1e-2, "{}", i);
+        }
+    }
+
+    #[test]
+    fn test_resampler_attenuates_above_output_nyquist() {
+        // A 30 kHz tone at 96 kHz cannot be represented at 44.1 kHz.
+        let input: Vec<f32> = (0..9600)
+            .map(|i| (2. * PI * 30000. * i as f32 / 96000.).sin())
+            .collect();
+        let output = Resampler::new(96000., 44100.).process(&input);
+        let peak = output[100..output.len() - 100]
+            .iter()
+            .fold(0f32, |peak, x| peak.max(x.abs()));
+        assert!(peak < 0.01, "{}", peak);
+    }
+}
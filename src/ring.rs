@@ -0,0 +1,80 @@
+use std::collections::VecDeque;
+
+// Fixed-capacity buffer holding the most recent values, newest first. Once
+// full, pushing a value drops the oldest one.
+pub struct RingBuffer<T> {
+    values: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T: Copy> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            values: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    // Shrinking drops the oldest values that no longer fit.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.values.truncate(capacity);
+        self.values
+            .reserve(capacity.saturating_sub(self.values.len()));
+        self.capacity = capacity;
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.values.len() == self.capacity {
+            self.values.pop_back();
+        }
+        self.values.push_front(value);
+    }
+
+    // `index` 0 is the most recent value.
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.values.get(index).copied()
+    }
+
+    pub fn first(&self) -> Option<T> {
+        self.get(0)
+    }
+
+    // Up to `count` of the most recent values, newest first.
+    pub fn recent(&self, count: usize) -> Vec<T> {
+        self.values.iter().take(count).copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_drops_oldest() {
+        let mut ring = RingBuffer::new(3);
+        for i in 0..5 {
+            ring.push(i);
+        }
+        assert_eq!(ring.first(), Some(4));
+        assert_eq!(ring.get(2), Some(2));
+        assert_eq!(ring.get(3), None);
+        assert_eq!(ring.recent(2), vec![4, 3]);
+    }
+
+    #[test]
+    fn test_set_capacity() {
+        let mut ring = RingBuffer::new(4);
+        for i in 0..4 {
+            ring.push(i);
+        }
+        ring.set_capacity(2);
+        assert_eq!(ring.recent(4), vec![3, 2]);
+        ring.set_capacity(3);
+        ring.push(4);
+        ring.push(5);
+        assert_eq!(ring.recent(4), vec![5, 4, 3]);
+    }
+}
@@ -0,0 +1,288 @@
+use super::utils::mean;
+
+const DEFAULT_MIN_BPM: f32 = 60.;
+const DEFAULT_MAX_BPM: f32 = 200.;
+const DEFAULT_PREFERRED_BPM: f32 = 120.;
+// Standard deviation of the tempo prior in octaves.
+const DEFAULT_PRIOR_WIDTH: f32 = 1.;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tempo {
+    pub bpm: f32,
+    // Normalized autocorrelation at the chosen lag, in [0, 1].
+    pub confidence: f32,
+}
+
+// Log-Gaussian weighting of candidate tempos, used to pick between a tempo
+// and its half or double, which autocorrelate almost equally well.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TempoPrior {
+    // Most likely tempo.
+    pub bpm: f32,
+    // Standard deviation in octaves; smaller values pull harder towards
+    // `bpm`.
+    pub width: f32,
+}
+
+impl Default for TempoPrior {
+    fn default() -> Self {
+        Self {
+            bpm: DEFAULT_PREFERRED_BPM,
+            width: DEFAULT_PRIOR_WIDTH,
+        }
+    }
+}
+
+impl TempoPrior {
+    fn weight(&self, bpm: f32) -> f32 {
+        let octaves = (bpm / self.bpm).log2() / self.width;
+        (-0.5 * octaves * octaves).exp()
+    }
+}
+
+// Range of tempos the estimator considers, and the prior used to choose
+// among them. Without a prior the strongest periodicity wins.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TempoParams {
+    pub min_bpm: f32,
+    pub max_bpm: f32,
+    pub prior: Option<TempoPrior>,
+}
+
+impl Default for TempoParams {
+    fn default() -> Self {
+        Self {
+            min_bpm: DEFAULT_MIN_BPM,
+            max_bpm: DEFAULT_MAX_BPM,
+            prior: Some(TempoPrior::default()),
+        }
+    }
+}
+
+impl TempoParams {
+    // Whether the range is positive and non-empty and the prior, if any, is
+    // centred on a positive tempo with a positive width.
+    pub fn is_valid(&self) -> bool {
+        let prior = self
+            .prior
+            .is_none_or(|prior| prior.bpm > 0. && prior.width > 0.);
+        self.min_bpm > 0. && self.min_bpm < self.max_bpm && prior
+    }
+}
+
+// Estimates tempo from an onset detection function by autocorrelation.
+pub struct TempoEstimator {
+    // ODF values per second.
+    frame_rate: f32,
+    params: TempoParams,
+}
+
+impl TempoEstimator {
+    pub fn new(frame_rate: f32, params: TempoParams) -> Self {
+        assert!(params.is_valid(), "invalid tempo parameters");
+        Self { frame_rate, params }
+    }
+
+    fn bpm_to_lag(&self, bpm: f32) -> f32 {
+        60. * self.frame_rate / bpm
+    }
+
+    fn lag_to_bpm(&self, lag: f32) -> f32 {
+        60. * self.frame_rate / lag
+    }
+
+    // `odf` must be evenly spaced in time; its ordering (oldest or newest
+    // first) does not matter. Returns `None` until at least two periods of
+    // the slowest tempo are available, or when the signal is flat.
+    pub fn estimate(&self, odf: &[f32]) -> Option<Tempo> {
+        let (min_lag, max_lag) = self.lag_range();
+        let acf = self.acf(odf)?;
+        let energy = acf[0];
+        if energy <= 0. {
+            return None;
+        }
+
+        // Only local maxima are candidates, so the prior chooses between
+        // periodicities rather than shifting a peak along its slope.
+        let candidates: Vec<usize> = (min_lag..=max_lag)
+            .filter(|&lag| acf[lag] > 0. && acf[lag] >= acf[lag - 1] && acf[lag] >= acf[lag + 1])
+            .collect();
+        let score = |lag: usize| match self.params.prior {
+            Some(prior) => acf[lag] * prior.weight(self.lag_to_bpm(lag as f32)),
+            None => acf[lag],
+        };
+        let best_lag = candidates
+            .into_iter()
+            .max_by(|&a, &b| score(a).total_cmp(&score(b)))?;
+
+        // Parabolic interpolation around the peak for sub-frame resolution.
+        let (left, peak, right) = (acf[best_lag - 1], acf[best_lag], acf[best_lag + 1]);
+        let denominator = left - 2. * peak + right;
+        let offset = match denominator.abs() > f32::EPSILON {
+            true => (0.5 * (left - right) / denominator).clamp(-0.5, 0.5),
+            false => 0.,
+        };
+
+        Some(Tempo {
+            bpm: self
+                .lag_to_bpm(best_lag as f32 + offset)
+                .clamp(self.params.min_bpm, self.params.max_bpm),
+            confidence: (peak / energy).clamp(0., 1.),
+        })
+    }
+
+    // Tempos of the tempogram rows, in steps of 1 BPM across the range.
+    pub fn tempogram_bpms(&self) -> Vec<f32> {
+        let first = self.params.min_bpm.ceil() as usize;
+        let last = self.params.max_bpm.floor() as usize;
+        (first..=last).map(|bpm| bpm as f32).collect()
+    }
+
+    // One tempogram column: the normalized autocorrelation of `odf` at the
+    // lag of each tempo in `tempogram_bpms`, in [0, 1]. Has the same
+    // history requirement as `estimate`; a flat signal gives all zeros.
+    pub fn tempogram_column(&self, odf: &[f32]) -> Option<Vec<f32>> {
+        let acf = self.acf(odf)?;
+        let energy = acf[0];
+        Some(
+            self.tempogram_bpms()
+                .into_iter()
+                .map(|bpm| {
+                    let lag = self.bpm_to_lag(bpm);
+                    let (index, fraction) = (lag.floor() as usize, lag.fract());
+                    let value = acf[index] + fraction * (acf[index + 1] - acf[index]);
+                    match energy > 0. {
+                        true => (value / energy).clamp(0., 1.),
+                        false => 0.,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn lag_range(&self) -> (usize, usize) {
+        let min_lag = self.bpm_to_lag(self.params.max_bpm).floor().max(1.) as usize;
+        let max_lag = self.bpm_to_lag(self.params.min_bpm).ceil() as usize;
+        (min_lag, max_lag)
+    }
+
+    // Autocorrelation of the mean-centred ODF for lags up to one past the
+    // slowest tempo's, or `None` without two periods of that tempo.
+    fn acf(&self, odf: &[f32]) -> Option<Vec<f32>> {
+        let (_, max_lag) = self.lag_range();
+        if odf.len() < max_lag * 2 {
+            return None;
+        }
+        let centre = mean(odf);
+        let centred: Vec<f32> = odf.iter().map(|x| x - centre).collect();
+        Some(
+            (0..=max_lag + 1)
+                .map(|lag| autocorrelation(&centred, lag))
+                .collect(),
+        )
+    }
+}
+
+// Biased autocorrelation of `signal` at `lag`. Dividing by the full length
+// rather than the overlap slightly favours shorter lags, which breaks ties
+// between a period and its multiples.
+fn autocorrelation(signal: &[f32], lag: usize) -> f32 {
+    let overlap = signal.len() - lag;
+    let sum: f32 = signal[..overlap]
+        .iter()
+        .zip(signal[lag..].iter())
+        .map(|(a, b)| a * b)
+        .sum();
+    sum / signal.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::pulse_train;
+
+    #[test]
+    fn test_estimate_pulse_train() {
+        // 100 frames per second, a pulse every 50 frames is 120 BPM.
+        let estimator = TempoEstimator::new(100., TempoParams::default());
+        let tempo = estimator.estimate(&pulse_train(1000, 50)).unwrap();
+        assert!((tempo.bpm - 120.).abs() < 1., "{}", tempo.bpm);
+        assert!(tempo.confidence > 0.5);
+    }
+
+    #[test]
+    fn test_estimate_needs_history() {
+        let estimator = TempoEstimator::new(100., TempoParams::default());
+        assert_eq!(estimator.estimate(&pulse_train(50, 50)), None);
+        assert_eq!(estimator.estimate(&[0.; 1000]), None);
+    }
+
+    #[test]
+    fn test_tempo_params_validity() {
+        assert!(TempoParams::default().is_valid());
+        let range = |min_bpm, max_bpm| TempoParams {
+            min_bpm,
+            max_bpm,
+            ..TempoParams::default()
+        };
+        assert!(!range(210., 200.).is_valid());
+        assert!(!range(0., 200.).is_valid());
+        assert!(!TempoParams {
+            prior: Some(TempoPrior {
+                bpm: -1.,
+                width: 1.
+            }),
+            ..TempoParams::default()
+        }
+        .is_valid());
+    }
+
+    #[test]
+    fn test_prior_resolves_octave_errors() {
+        // Strong hits at 70 BPM with weaker ones in between, as on the
+        // off-beats of a 140 BPM track.
+        let odf: Vec<f32> = (0..2000)
+            .map(|i| match i % 86 {
+                0 => 1.,
+                43 => 0.5,
+                _ => 0.,
+            })
+            .collect();
+        let bpm = |params| {
+            TempoEstimator::new(100., params)
+                .estimate(&odf)
+                .unwrap()
+                .bpm
+        };
+        let unweighted = TempoParams {
+            prior: None,
+            ..TempoParams::default()
+        };
+        assert!((bpm(unweighted) - 70.).abs() < 1., "{}", bpm(unweighted));
+        assert!((bpm(TempoParams::default()) - 140.).abs() < 2.);
+
+        // The range excludes the slower reading even without a prior.
+        let range = TempoParams {
+            min_bpm: 90.,
+            ..unweighted
+        };
+        assert!((bpm(range) - 140.).abs() < 2., "{}", bpm(range));
+    }
+
+    #[test]
+    fn test_tempogram_column() {
+        let estimator = TempoEstimator::new(100., TempoParams::default());
+        let bpms = estimator.tempogram_bpms();
+        assert_eq!(bpms.len(), 141);
+        assert_eq!((bpms[0], bpms[140]), (60., 200.));
+
+        let column = estimator.tempogram_column(&pulse_train(1000, 50)).unwrap();
+        assert_eq!(column.len(), bpms.len());
+        let strongest = (0..column.len())
+            .max_by(|&a, &b| column[a].total_cmp(&column[b]))
+            .unwrap();
+        assert_eq!(bpms[strongest], 120.);
+        assert_eq!(estimator.tempogram_column(&[0.; 1000]), Some(vec![0.; 141]));
+        assert_eq!(estimator.tempogram_column(&[0.; 100]), None);
+    }
+}
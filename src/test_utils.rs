@@ -0,0 +1,24 @@
+// Synthetic signals shared by the tests.
+
+use std::f32::consts::PI;
+
+// Decaying 1 kHz bursts at `bpm` for `seconds`.
+pub fn clicks(bpm: f32, seconds: f32, sample_rate: f32) -> Vec<f32> {
+    let period = (sample_rate * 60. / bpm) as usize;
+    (0..(sample_rate * seconds) as usize)
+        .map(|i| {
+            let t = (i % period) as f32 / sample_rate;
+            (-t * 40.).exp() * (2. * PI * 1000. * t).sin()
+        })
+        .collect()
+}
+
+// Unit impulses `period` values apart, starting with one.
+pub fn pulse_train(len: usize, period: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| match i % period {
+            0 => 1.,
+            _ => 0.,
+        })
+        .collect()
+}
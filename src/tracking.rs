@@ -0,0 +1,181 @@
+use super::tempo::Tempo;
+
+// Estimates this close to twice or half the tracked tempo, in octaves, are
+// treated as octave errors and folded back onto it.
+const OCTAVE_TOLERANCE: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TrackingParams {
+    // Seconds between successive windowed tempo estimates.
+    pub step: f32,
+    // Weight of each new estimate in the smoothed tempo, in (0, 1].
+    pub smoothing: f32,
+    // Relative deviation from the smoothed tempo treated as a tempo change
+    // rather than drift.
+    pub change_threshold: f32,
+    // Number of consecutive, mutually agreeing deviating estimates needed to
+    // report a change. Fewer are discarded as outliers.
+    pub change_windows: usize,
+}
+
+impl Default for TrackingParams {
+    fn default() -> Self {
+        Self {
+            step: 1.,
+            smoothing: 0.3,
+            change_threshold: 0.04,
+            change_windows: 3,
+        }
+    }
+}
+
+// Smoothed tempo at the end of one estimation window.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TempoPoint {
+    // ODF frame the estimate was made at.
+    pub frame: usize,
+    pub time: f32,
+    pub tempo: Tempo,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TempoChange {
+    // ODF frame at which the change was confirmed.
+    pub frame: usize,
+    pub time: f32,
+    pub from_bpm: f32,
+    pub to_bpm: f32,
+}
+
+// Smooths a sequence of windowed tempo estimates and detects abrupt tempo
+// changes. Small deviations are followed gradually, so the tempo tracks a
+// drifting performance; sustained large ones jump to the new tempo at once.
+pub struct TempoTracker {
+    params: TrackingParams,
+    smoothed: Option<Tempo>,
+    // Consecutive estimates that deviate from the smoothed tempo.
+    deviating: Vec<Tempo>,
+}
+
+impl TempoTracker {
+    pub fn new(params: TrackingParams) -> Self {
+        assert!(
+            params.smoothing > 0. && params.smoothing <= 1.,
+            "smoothing must be in (0, 1]"
+        );
+        Self {
+            params,
+            smoothed: None,
+            deviating: vec![],
+        }
+    }
+
+    pub fn tempo(&self) -> Option<Tempo> {
+        self.smoothed
+    }
+
+    // Feeds the estimate of the next window and returns the tempo change it
+    // confirms, if any, as `(from_bpm, to_bpm)`.
+    pub fn update(&mut self, estimate: Tempo) -> Option<(f32, f32)> {
+        let current = match self.smoothed {
+            Some(current) => current,
+            None => {
+                self.smoothed = Some(estimate);
+                return None;
+            }
+        };
+        let estimate = fold_octave(estimate, current.bpm);
+        let threshold = (1. + self.params.change_threshold).log2();
+        if octaves(estimate.bpm, current.bpm).abs() <= threshold {
+            self.deviating.clear();
+            self.smoothed = Some(blend(current, estimate, self.params.smoothing));
+            return None;
+        }
+
+        // Deviating estimates only count towards a change while they agree
+        // with each other.
+        match self.deviating.first() {
+            Some(first) if octaves(estimate.bpm, first.bpm).abs() > threshold => {
+                self.deviating.clear()
+            }
+            _ => {}
+        }
+        self.deviating.push(estimate);
+        if self.deviating.len() < self.params.change_windows.max(1) {
+            return None;
+        }
+        let count = self.deviating.len() as f32;
+        let log_bpm = self.deviating.iter().map(|t| t.bpm.log2()).sum::<f32>() / count;
+        let confidence = self.deviating.iter().map(|t| t.confidence).sum::<f32>() / count;
+        let to = Tempo {
+            bpm: log_bpm.exp2(),
+            confidence,
+        };
+        self.deviating.clear();
+        self.smoothed = Some(to);
+        Some((current.bpm, to.bpm))
+    }
+}
+
+fn octaves(bpm: f32, reference: f32) -> f32 {
+    (bpm / reference).log2()
+}
+
+fn fold_octave(estimate: Tempo, reference: f32) -> Tempo {
+    let octave = octaves(estimate.bpm, reference).round();
+    match octave != 0. && (octaves(estimate.bpm, reference) - octave).abs() < OCTAVE_TOLERANCE {
+        true => Tempo {
+            bpm: estimate.bpm / octave.exp2(),
+            ..estimate
+        },
+        false => estimate,
+    }
+}
+
+// Exponential smoothing in the log domain, so speeding up and slowing down
+// by the same ratio are treated alike.
+fn blend(current: Tempo, estimate: Tempo, weight: f32) -> Tempo {
+    let log_bpm = current.bpm.log2() + weight * (estimate.bpm.log2() - current.bpm.log2());
+    Tempo {
+        bpm: log_bpm.exp2(),
+        confidence: current.confidence + weight * (estimate.confidence - current.confidence),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tempo(bpm: f32) -> Tempo {
+        Tempo {
+            bpm,
+            confidence: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_tracker_follows_drift_and_ignores_outliers() {
+        let mut tracker = TempoTracker::new(TrackingParams::default());
+        assert_eq!(tracker.update(tempo(120.)), None);
+        // Octave errors and a single outlier leave the tempo alone.
+        assert_eq!(tracker.update(tempo(60.)), None);
+        assert_eq!(tracker.update(tempo(150.)), None);
+        assert!((tracker.tempo().unwrap().bpm - 120.).abs() < 1e-3);
+        for _ in 0..20 {
+            assert_eq!(tracker.update(tempo(122.)), None);
+        }
+        assert!((tracker.tempo().unwrap().bpm - 122.).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_tracker_reports_tempo_changes() {
+        let mut tracker = TempoTracker::new(TrackingParams::default());
+        tracker.update(tempo(120.));
+        assert_eq!(tracker.update(tempo(140.)), None);
+        assert_eq!(tracker.update(tempo(140.)), None);
+        let (from, to) = tracker.update(tempo(140.)).unwrap();
+        assert_eq!(from, 120.);
+        assert!((to - 140.).abs() < 0.01, "{}", to);
+        assert_eq!(tracker.update(tempo(140.)), None);
+    }
+}
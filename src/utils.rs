@@ -17,9 +17,9 @@ pub fn mean(set: &[f32]) -> f32 {
 pub fn median(set: &[f32]) -> f32 {
     let mut copy = vec![0.; set.len()];
     copy[..].clone_from_slice(set);
-    copy.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    copy.sort_by(f32::total_cmp);
     let middle_index = copy.len() / 2;
-    if copy.len() % 2 == 0 {
+    if copy.len().is_multiple_of(2) {
         return mean(&copy[middle_index - 1..middle_index]);
     }
     copy[middle_index]
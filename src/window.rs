@@ -0,0 +1,56 @@
+use std::f32::consts::PI;
+use wasm_bindgen::prelude::*;
+
+// Window functions applied to frames before energy and spectral analysis.
+#[wasm_bindgen]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+    BlackmanHarris,
+}
+
+impl Window {
+    // Periodic window coefficients for a frame of `size` samples.
+    pub fn coefficients(&self, size: usize) -> Vec<f32> {
+        (0..size)
+            .map(|i| {
+                let x = 2. * PI * i as f32 / size as f32;
+                match self {
+                    Window::Rectangular => 1.,
+                    Window::Hann => 0.5 - 0.5 * x.cos(),
+                    Window::Hamming => 0.54 - 0.46 * x.cos(),
+                    Window::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2. * x).cos(),
+                    Window::BlackmanHarris => {
+                        0.35875 - 0.48829 * x.cos() + 0.14128 * (2. * x).cos()
+                            - 0.01168 * (3. * x).cos()
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_shapes() {
+        assert_eq!(Window::Rectangular.coefficients(4), vec![1.; 4]);
+        for window in &[
+            Window::Hann,
+            Window::Hamming,
+            Window::Blackman,
+            Window::BlackmanHarris,
+        ] {
+            let coefficients = window.coefficients(64);
+            // Peak in the middle, tapering towards the edges.
+            assert!((coefficients[32] - 1.).abs() < 1e-3, "{:?}", window);
+            assert!(coefficients[0] < 0.1, "{:?}", window);
+            assert!((coefficients[1] - coefficients[63]).abs() < 1e-5);
+        }
+    }
+}
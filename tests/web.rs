@@ -11,3 +11,89 @@ wasm_bindgen_test_configure!(run_in_browser);
 fn pass() {
     assert_eq!(1 + 1, 2);
 }
+
+#[wasm_bindgen_test]
+fn onset_detector_buffers_partial_input() {
+    let mut detector = bpm::OnsetDetector::new(None, None, None, None).unwrap();
+    assert!(!detector.process(&[0.; 128]));
+    assert_eq!(detector.tempo(), None);
+    assert_eq!(detector.odf(), 0.);
+    assert!(!detector.ready());
+}
+
+#[wasm_bindgen_test]
+fn onset_detector_accepts_planar_input() {
+    let mut detector = bpm::OnsetDetector::new(None, None, None, None).unwrap();
+    detector.set_downmix(bpm::Downmix::Side);
+    assert!(!detector.process_planar(&[0.; 256], 2));
+    assert!(!detector.process_interleaved(&[0.; 256], 2));
+    assert_eq!(detector.downmix(), bpm::Downmix::Side);
+}
+
+#[wasm_bindgen_test]
+fn onset_detector_ignores_invalid_tempo_range() {
+    let mut detector = bpm::OnsetDetector::new(None, None, None, None).unwrap();
+    detector.set_min_bpm(210.);
+    assert_eq!(detector.min_bpm(), 60.);
+    assert!(detector.set_tempo_range(210., 300.));
+    assert_eq!((detector.min_bpm(), detector.max_bpm()), (210., 300.));
+    assert!(!detector.set_tempo_range(0., 100.));
+}
+
+#[wasm_bindgen_test]
+fn onset_detector_clamps_threshold_window() {
+    let mut detector = bpm::OnsetDetector::new(None, None, None, None).unwrap();
+    detector.set_threshold_window(0);
+    assert_eq!(detector.threshold_window(), 1);
+    assert!(!detector.process(&[0.; 1024]));
+}
+
+#[wasm_bindgen_test]
+fn onset_detector_clamps_beats_per_bar() {
+    let mut detector = bpm::OnsetDetector::new(None, None, None, None).unwrap();
+    detector.set_beats_per_bar(0);
+    assert_eq!(detector.beats_per_bar(), 1);
+}
+
+#[wasm_bindgen_test]
+fn onset_detector_ignores_bands_past_the_limit() {
+    let mut detector = bpm::OnsetDetector::new(None, None, None, None).unwrap();
+    let bands: Vec<f32> = (0..bpm::MAX_BANDS + 1)
+        .flat_map(|i| [i as f32 * 100., (i + 1) as f32 * 100., 1.])
+        .collect();
+    detector.set_bands(&bands);
+    assert!(!detector.process(&[0.; 128]));
+}
+
+#[wasm_bindgen_test]
+fn onset_detector_rejects_invalid_sizes() {
+    assert!(bpm::OnsetDetector::new(None, Some(0.), None, None).is_err());
+    assert!(bpm::OnsetDetector::new(None, None, Some(0), None).is_err());
+    assert!(bpm::OnsetDetector::new(None, None, None, Some(0)).is_err());
+}
+
+#[wasm_bindgen_test]
+fn onset_detector_sets_analysis_rate() {
+    let mut detector = bpm::OnsetDetector::new(None, Some(48000.), None, None).unwrap();
+    detector.set_analysis_rate(None);
+    assert_eq!(detector.analysis_rate(), None);
+    detector.set_analysis_rate(Some(0.));
+    assert_eq!(detector.analysis_rate(), None);
+    detector.set_analysis_rate(Some(22050.));
+    assert_eq!(detector.analysis_rate(), Some(22050.));
+    assert!(!detector.process(&[0.; 128]));
+}
+
+#[wasm_bindgen_test]
+fn onset_detector_processes_channels_separately() {
+    let mut detector = bpm::OnsetDetector::new(None, None, None, None).unwrap();
+    detector.set_beats_per_bar(3);
+    detector.set_per_channel(true);
+    assert!(detector.per_channel());
+    assert_eq!(detector.beats_per_bar(), 3);
+    assert!(!detector.process_planar(&[0.; 256], 2));
+    assert!(!detector.process_interleaved(&[0.; 384], 3));
+    detector.set_downmix(bpm::Downmix::Mid);
+    assert!(detector.per_channel());
+    assert_eq!(detector.downmix(), bpm::Downmix::Mid);
+}